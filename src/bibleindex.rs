@@ -0,0 +1,269 @@
+use crate::bibleverse::versereader;
+use std::{
+    fs::{self, File},
+    io::{self, prelude::*, SeekFrom},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+// Written at the start of every index file, bump it when the layout changes
+const INDEX_MAGIC: &[u8; 8] = b"E4BIDX2\n";
+
+// Bytes of the index before the offsets: magic, size, mtime, nanoseconds and count
+const INDEX_HEADER_SIZE: u64 = 40;
+
+// Bytes of each indexed verse: offset and line number
+const INDEX_ENTRY_SIZE: u64 = 16;
+
+// Extension of the index stored next to the bible file
+const INDEX_EXTENSION: &str = "idx";
+
+// Extension of the decompressed copy of a compressed bible file
+const COPY_EXTENSION: &str = "txt";
+
+// Extension of the size and modification time of the compressed file of a copy
+const COPY_STAMP_EXTENSION: &str = "stamp";
+
+/// Byte offsets of every verse of a bible file, so a random verse
+/// can be read with a single seek instead of a full scan.
+pub struct BibleIndex {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    offsets: Vec<u64>,
+    // Line number in the file of each offset, for error reporting
+    line_numbers: Vec<u64>,
+}
+
+impl BibleIndex {
+    /// Loads a fresh index for `bible_path` from one of the cache locations,
+    /// or builds it and tries to save it for the next run.
+    pub fn load_or_build(bible_path: &Path) -> io::Result<Self> {
+        let (size, mtime_secs, mtime_nanos) = Self::stamp(bible_path)?;
+
+        for candidate in Self::candidates(bible_path) {
+            if let Ok(index) = Self::load(&candidate) {
+                if index.size == size
+                    && index.mtime_secs == mtime_secs
+                    && index.mtime_nanos == mtime_nanos
+                {
+                    return Ok(index);
+                }
+            }
+        }
+
+        let index = Self::build(bible_path)?;
+        // The index is only a cache: if no location is writable just use it in memory
+        for candidate in Self::candidates(bible_path) {
+            if index.save(&candidate).is_ok() {
+                break;
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// A decompressed copy of a compressed bible file, kept in the cache directory so it
+    /// can be indexed. The copy is written again when the size or the modification time
+    /// of the compressed file change, even to an older time, e.g. when it is restored.
+    pub fn decompressed_copy(bible_path: &Path) -> io::Result<PathBuf> {
+        let no_cache = || io::Error::new(io::ErrorKind::NotFound, "no cache directory");
+        let copy_path = Self::cache_path(bible_path, COPY_EXTENSION).ok_or_else(no_cache)?;
+        let stamp_path = Self::cache_path(bible_path, COPY_STAMP_EXTENSION).ok_or_else(no_cache)?;
+
+        let (size, mtime_secs, mtime_nanos) = Self::stamp(bible_path)?;
+        let stamp = format!("{} {} {}\n", size, mtime_secs, mtime_nanos);
+        if copy_path.is_file() && fs::read_to_string(&stamp_path).is_ok_and(|s| s == stamp) {
+            return Ok(copy_path);
+        }
+
+        if let Some(parent) = copy_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first so a concurrent reader never sees half a copy
+        let mut tmp_path = copy_path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let mut reader = versereader::BufReader::open(bible_path)?;
+        let mut writer = io::BufWriter::new(File::create(&tmp_path)?);
+        io::copy(&mut reader, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(&tmp_path, &copy_path)?;
+        // Written last: the stamp of a copy never matches before the copy is complete
+        fs::write(&stamp_path, stamp)?;
+        Ok(copy_path)
+    }
+
+    /// Number of indexed verses
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Reads the verse line number `n` (counting only indexed lines) together with
+    /// its line number in the file. Returns None if the file changed since the index was built.
+    pub fn read_line(&self, bible_path: &Path, n: usize) -> io::Result<Option<(usize, String)>> {
+        let (size, mtime_secs, mtime_nanos) = Self::stamp(bible_path)?;
+        if size != self.size || mtime_secs != self.mtime_secs || mtime_nanos != self.mtime_nanos {
+            return Ok(None);
+        }
+
+        let Some(offset) = self.offsets.get(n) else {
+            return Ok(None);
+        };
+
+        let mut reader = io::BufReader::new(File::open(bible_path)?);
+        reader.seek(SeekFrom::Start(*offset))?;
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some((self.line_numbers[n] as usize, line)))
+    }
+
+    // Size and modification time used to tell whether an index is stale
+    fn stamp(bible_path: &Path) -> io::Result<(u64, u64, u32)> {
+        let metadata = fs::metadata(bible_path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Ok((metadata.len(), mtime.as_secs(), mtime.subsec_nanos()))
+    }
+
+    // Index locations, in order of preference: next to the bible file,
+    // then under $XDG_CACHE_HOME (or ~/.cache) for read-only installations
+    fn candidates(bible_path: &Path) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+
+        let mut sidecar = bible_path.as_os_str().to_owned();
+        sidecar.push(".");
+        sidecar.push(INDEX_EXTENSION);
+        candidates.push(PathBuf::from(sidecar));
+        candidates.extend(Self::cache_path(bible_path, INDEX_EXTENSION));
+
+        candidates
+    }
+
+    // A file about the bible under $XDG_CACHE_HOME/e4biblesaver (or ~/.cache/e4biblesaver)
+    fn cache_path(bible_path: &Path, extension: &str) -> Option<PathBuf> {
+        let mut cache_dir = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+
+        let absolute = fs::canonicalize(bible_path).unwrap_or_else(|_| bible_path.to_path_buf());
+        // Flatten the full path so different bibles get different cache files
+        let name = absolute.to_string_lossy().replace('/', "%");
+        cache_dir.push("e4biblesaver");
+        cache_dir.push(format!("{}.{}", name, extension));
+
+        Some(cache_dir)
+    }
+
+    // Scans the bible file once, recording where each non empty line starts
+    fn build(bible_path: &Path) -> io::Result<Self> {
+        let (size, mtime_secs, mtime_nanos) = Self::stamp(bible_path)?;
+        let mut reader = io::BufReader::new(File::open(bible_path)?);
+
+        let mut offsets = Vec::new();
+        let mut line_numbers = Vec::new();
+        let mut offset: u64 = 0;
+        let mut line_number: u64 = 0;
+        let mut buffer = Vec::new();
+        loop {
+            buffer.clear();
+            let read = reader.read_until(b'\n', &mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            line_number += 1;
+            if !buffer.iter().all(u8::is_ascii_whitespace) {
+                offsets.push(offset);
+                line_numbers.push(line_number);
+            }
+            offset += read as u64;
+        }
+
+        Ok(Self {
+            size,
+            mtime_secs,
+            mtime_nanos,
+            offsets,
+            line_numbers,
+        })
+    }
+
+    fn load(index_path: &Path) -> io::Result<Self> {
+        let file = File::open(index_path)?;
+        let file_size = file.metadata()?.len();
+        let mut reader = io::BufReader::new(file);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a bible index"));
+        }
+
+        let size = Self::read_u64(&mut reader)?;
+        let mtime_secs = Self::read_u64(&mut reader)?;
+        let mtime_nanos = Self::read_u64(&mut reader)? as u32;
+        let count = Self::read_u64(&mut reader)?;
+        // A corrupted count must not allocate anything, the index is built again instead
+        if count.checked_mul(INDEX_ENTRY_SIZE) != file_size.checked_sub(INDEX_HEADER_SIZE) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Truncated bible index"));
+        }
+        let count = count as usize;
+
+        let mut offsets = Vec::with_capacity(count);
+        let mut line_numbers = Vec::with_capacity(count);
+        for _ in 0..count {
+            offsets.push(Self::read_u64(&mut reader)?);
+            line_numbers.push(Self::read_u64(&mut reader)?);
+        }
+
+        Ok(Self {
+            size,
+            mtime_secs,
+            mtime_nanos,
+            offsets,
+            line_numbers,
+        })
+    }
+
+    fn save(&self, index_path: &Path) -> io::Result<()> {
+        if let Some(parent) = index_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temporary file first so a concurrent reader never sees half an index
+        let mut tmp_path = index_path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let mut writer = io::BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_all(&self.size.to_le_bytes())?;
+        writer.write_all(&self.mtime_secs.to_le_bytes())?;
+        writer.write_all(&(self.mtime_nanos as u64).to_le_bytes())?;
+        writer.write_all(&(self.offsets.len() as u64).to_le_bytes())?;
+        for (offset, line_number) in self.offsets.iter().zip(&self.line_numbers) {
+            writer.write_all(&offset.to_le_bytes())?;
+            writer.write_all(&line_number.to_le_bytes())?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(&tmp_path, index_path)
+    }
+
+    fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        reader.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
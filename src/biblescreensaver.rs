@@ -1,16 +1,30 @@
 use crate::bibleverse::BibleVerse;
 use crate::bibleverse::Direction;
-use rand::Rng;
+use crate::bibleverse::Selection;
+use crate::colors::Colors;
+use crate::monitors::{self, Area};
+use crate::translation::Translation;
+use crate::verse;
+use crate::versehistory::VerseHistory;
+use crate::versesource::VerseSource;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::ffi::CString;
 use std::mem::MaybeUninit;
 use x11::{
-    xft::{XftColor, XftDrawCreate, XftDrawStringUtf8, XftFontOpenName, XftTextExtentsUtf8},
+    xft::{
+        XftDraw, XftDrawChange, XftDrawCreate, XftDrawDestroy, XftDrawStringUtf8,
+        XftFont, XftFontClose, XftFontOpenName, XftTextExtentsUtf8,
+    },
     xlib::{
-        Display, Window, XBlackPixelOfScreen, XClearArea, XCreateGC, XCreateSimpleWindow,
-        XDefaultScreenOfDisplay, XFlush, XGetWindowAttributes, XMapWindow, XOpenDisplay,
-        XRootWindowOfScreen, XScreenCount, XSetForeground, XWhitePixelOfScreen, XWindowAttributes,
+        self, Atom, Colormap, Display, ExposureMask, Pixmap, StructureNotifyMask, Visual, Window,
+        XCloseDisplay, XCopyArea, XCreateGC, XCreatePixmap, XCreateSimpleWindow,
+        XDefaultColormapOfScreen, XDefaultScreenOfDisplay, XDefaultVisualOfScreen, XEvent,
+        XFillRectangle, XFlush, XFreeGC, XFreePixmap, XGCValues, XGetWindowAttributes,
+        XInternAtom, XMapWindow, XNextEvent, XOpenDisplay, XPending, XRootWindowOfScreen,
+        XScreenCount, XSelectInput, XSetWMProtocols, XSetWindowBackground, XWindowAttributes, GC,
+        GCForeground, GCGraphicsExposures,
     },
-    xrender::{XGlyphInfo, XRenderColor},
+    xrender::XGlyphInfo,
 };
 
 // Move the verse of n pixels at each update
@@ -19,24 +33,109 @@ const FLUTTUATION_SIZE: i32 = 3;
 // The real font size in pixels will be screen_width / FONTSIZE_FACTOR / give setting font width
 const FONTSIZE_FACTOR: f64 = 10.0_f64;
 
+// The font is never shrunk below this size to fit long passages
+const MIN_FONT_SIZE: i32 = 6;
+
+/// Used when a font is not given or cannot be opened
+pub const DEFAULT_FONT: &str = "Sans";
+
 // Update the verse each n milliseconds
 const FPS: u64 = 50;
 
+/// How the translations of a verse are placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Stacked,
+    SideBySide,
+}
+
+/// How the verses look on the screen
+#[derive(Debug, Clone)]
+pub struct Appearance {
+    pub line_length: i32, // In characters
+    pub font_size: i32, // Relative to the monitor width, see calculate_font_size
+    pub layout: Layout,
+    // X color names, #rrggbb or rgba:rr/gg/bb/aa
+    pub foreground: String,
+    pub reference_color: String,
+    pub background: String,
+    // Fontconfig patterns, e.g. DejaVu Serif:italic
+    pub font: String,
+    pub reference_font: String,
+}
+
 #[link(name = "X11")]
 #[link(name = "Xft")]
 extern "C" {}
 
+// The fonts of the text and of the references, opened at the same size
+#[derive(Clone, Copy)]
+struct Fonts {
+    text: *mut XftFont,
+    reference: *mut XftFont,
+}
+
+// A line of the passages placed on the screen
+struct Line {
+    x: i32,
+    row: i32,
+    // The part of the reference at the start of the line, if any, and its width
+    reference: String,
+    reference_width: i32,
+    text: String,
+}
+
+// The lines of the passages placed on the screen
+struct TextBlock {
+    lines: Vec<Line>,
+    width: i32,
+    line_height: i32,
+    rows: i32,
+}
+
+// What happened to the window since the last frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WindowChange {
+    Unchanged,
+    Resized,
+    Closed,
+}
+
 pub struct ScreensaverSetup {
     display: *mut Display,
     window_id: Window,
+    // The frames are composed here and copied to the window in one operation
+    back_buffer: Pixmap,
+    // Fills with the background color and copies the back buffer
+    background_gc: GC,
+    // The colors are allocated for these
+    visual: *mut Visual,
+    colormap: Colormap,
+    colors: Colors,
+    // Where the text of the last frame was drawn
+    drawn: Option<Area>,
+    // The area of each monitor, a verse is shown in one of them
+    monitors: Vec<Area>,
+    // Sent by the window manager when the window is closed, 0 in the xscreensaver window
+    wm_delete_window: Atom,
     height: i32,
     width: i32,
     verse_x: i32,
     verse_y: i32,
     line_length: i32, // In characters
-    font_size: i32, // In pixels
-    bible_path: String,
+    font_size: i32, // Relative to the monitor width, see calculate_font_size
+    font: String,
+    reference_font: String,
+    layout: Layout,
+    source: Box<dyn VerseSource>,
     duration: u64,
+    rng: StdRng,
+    // Moves the verses: the number of frames of a verse depends on the clock,
+    // so the animation must not change the verses chosen by rng
+    animation_rng: StdRng,
+    history: VerseHistory,
+    selection: Selection,
+    translations: Vec<Translation>,
 }
 
 impl ScreensaverSetup {
@@ -45,11 +144,25 @@ impl ScreensaverSetup {
     }
 
     pub fn new(
-        line_length: i32,
-        font_size: i32,
-        bible_path: String,
+        appearance: Appearance,
+        source: Box<dyn VerseSource>,
         speed: u64,
-    ) -> Result<Self, ()> {
+        seed: Option<u64>,
+        history_size: usize,
+        selection: Selection,
+        translations: Vec<Translation>,
+    ) -> Result<Self, (Box<dyn VerseSource>, Vec<Translation>)> {
+        let Appearance {
+            line_length,
+            font_size,
+            layout,
+            foreground,
+            reference_color,
+            background,
+            font,
+            reference_font,
+        } = appearance;
+
         let xscreensaver_id_str = std::env::var("XSCREENSAVER_WINDOW")
             .ok()
             .unwrap_or_default()
@@ -62,6 +175,30 @@ impl ScreensaverSetup {
         let xscreensaver_id = Window::from_str_radix(&xscreensaver_id_str, 16).ok();
         let display_string = CString::new("DISPLAY").unwrap();
         let display = unsafe { XOpenDisplay(libc::getenv(display_string.as_ptr())) };
+        if display.is_null() {
+            // No X server, give the verses back to the caller, which will print them instead
+            return Err((source, translations));
+        }
+
+        // A fixed seed reproduces the same verses, the same ones printed without an X server,
+        // and the same animation
+        let (rng, animation_rng) = match seed {
+            Some(seed) => (
+                StdRng::seed_from_u64(seed),
+                StdRng::seed_from_u64(seed.wrapping_add(1)),
+            ),
+            None => (StdRng::from_os_rng(), StdRng::from_os_rng()),
+        };
+
+        // The history of the previous runs would change the verses of a seed
+        let history = match seed {
+            Some(_) => VerseHistory::in_memory(history_size),
+            None => VerseHistory::load(history_size),
+        };
+
+        // Reported once here, the default font is used in its place afterwards
+        let font = Self::checked_font(display, font);
+        let reference_font = Self::checked_font(display, reference_font);
 
         match xscreensaver_id {
             Some(root_window_id) => {
@@ -71,30 +208,59 @@ impl ScreensaverSetup {
                     XGetWindowAttributes(display, root_window_id, attrs.as_mut_ptr());
                 }
                 let attrs2 = unsafe { attrs.assume_init() };
-                let g = unsafe { XCreateGC(display, root_window_id, 0, std::ptr::null_mut()) };
-                unsafe {
-                    XSetForeground(display, g, XWhitePixelOfScreen(XDefaultScreenOfDisplay(display)));
-                }
-                let g2 = unsafe { XCreateGC(display, root_window_id, 0, std::ptr::null_mut()) };
+                let colors = Colors::alloc(
+                    display,
+                    attrs2.visual,
+                    attrs2.colormap,
+                    &foreground,
+                    &reference_color,
+                    &background,
+                );
+                // xscreensaver resizes its window, e.g. when the screen resolution changes
                 unsafe {
-                    XSetForeground(display, g2, XBlackPixelOfScreen(XDefaultScreenOfDisplay(display)));
+                    XSelectInput(display, root_window_id, StructureNotifyMask | ExposureMask);
+                    XSetWindowBackground(display, root_window_id, colors.background.pixel);
                 }
-
-                // Calculate the font size in percentual of the window size
-                let calculated_font_size: i32 =
-                    Self::calculate_font_size(attrs2.width as f64, font_size as f64);
+                let background_gc =
+                    Self::create_background_gc(display, root_window_id, colors.background.pixel);
+                let back_buffer = Self::create_back_buffer(
+                    display,
+                    root_window_id,
+                    background_gc,
+                    attrs2.width,
+                    attrs2.height,
+                );
+                let monitors =
+                    monitors::query(display, root_window_id, attrs2.width, attrs2.height);
 
                 Ok(ScreensaverSetup {
                     display,
                     window_id: root_window_id,
+                    back_buffer,
+                    background_gc,
+                    visual: attrs2.visual,
+                    colormap: attrs2.colormap,
+                    colors,
+                    // The whole window is filled with the background at the first frame
+                    drawn: Some((0, 0, attrs2.width as u32, attrs2.height as u32)),
+                    monitors,
+                    wm_delete_window: 0,
                     height: attrs2.height,
                     width: attrs2.width,
                     verse_x: -1,
                     verse_y: -1,
                     line_length,
-                    font_size: calculated_font_size,
-                    bible_path,
+                    font_size,
+                    font,
+                    reference_font,
+                    layout,
+                    source,
                     duration: speed,
+                    rng,
+                    animation_rng,
+                    history,
+                    selection,
+                    translations,
                 })
             }
             None => {
@@ -102,6 +268,16 @@ impl ScreensaverSetup {
                 let height = 800;
                 let width = 1200;
                 let screen = unsafe { XDefaultScreenOfDisplay(display) };
+                let visual = unsafe { XDefaultVisualOfScreen(screen) };
+                let colormap = unsafe { XDefaultColormapOfScreen(screen) };
+                let colors = Colors::alloc(
+                    display,
+                    visual,
+                    colormap,
+                    &foreground,
+                    &reference_color,
+                    &background,
+                );
                 let win = unsafe {
                     XCreateSimpleWindow(
                         display,
@@ -111,90 +287,173 @@ impl ScreensaverSetup {
                         width,
                         height,
                         10,
-                        XBlackPixelOfScreen(screen),
-                        XBlackPixelOfScreen(screen),
+                        colors.background.pixel,
+                        colors.background.pixel,
                     )
                 };
 
-                let g = unsafe { XCreateGC(display, win, 0, std::ptr::null_mut()) };
-                unsafe {
-                    XSetForeground(display, g, XWhitePixelOfScreen(XDefaultScreenOfDisplay(display)));
-                }
-                let g2 = unsafe { XCreateGC(display, win, 0, std::ptr::null_mut()) };
-                unsafe {
-                    XSetForeground(display, g2, XBlackPixelOfScreen(XDefaultScreenOfDisplay(display)));
-                }
+                let background_gc =
+                    Self::create_background_gc(display, win, colors.background.pixel);
+                let back_buffer = Self::create_back_buffer(
+                    display,
+                    win,
+                    background_gc,
+                    width as i32,
+                    height as i32,
+                );
+                // Be told when the window is resized, uncovered or closed
+                let wm_delete_window = unsafe {
+                    let name = CString::new("WM_DELETE_WINDOW").unwrap();
+                    let mut atom = XInternAtom(display, name.as_ptr(), 0);
+                    XSetWMProtocols(display, win, &mut atom, 1);
+                    XSelectInput(display, win, StructureNotifyMask | ExposureMask);
+                    atom
+                };
                 unsafe {
                     XMapWindow(display, win);
                 }
-
-                // Calculate the font size in percentual of the window size
-                let calculated_font_size: i32 =
-                    Self::calculate_font_size(width as f64, font_size as f64);
+                let monitors = monitors::query(display, win, width as i32, height as i32);
                 Ok(ScreensaverSetup {
                     display,
                     window_id: win,
+                    back_buffer,
+                    background_gc,
+                    visual,
+                    colormap,
+                    colors,
+                    drawn: None,
+                    monitors,
+                    wm_delete_window,
                     height: height as i32,
                     width: width as i32,
                     verse_x: -1,
                     verse_y: -1,
                     line_length,
-                    font_size: calculated_font_size,
-                    bible_path,
+                    font_size,
+                    font,
+                    reference_font,
+                    layout,
+                    source,
                     duration: speed,
+                    rng,
+                    animation_rng,
+                    history,
+                    selection,
+                    translations,
                 })
             }
         }
     }
 
-    pub fn clear(&mut self, w: i32, h: i32) {
-        // Boundary, in pixels, added to each coordinate
-        let boundary: u32 = (self.width as f64 / 40.0_f64).round() as u32;
+    // A GC filling with the background, which does not ask for exposure events when copying
+    fn create_background_gc(display: *mut Display, window: Window, background: u64) -> GC {
+        let mut values: XGCValues = unsafe { std::mem::zeroed() };
+        values.foreground = background;
+        values.graphics_exposures = 0;
         unsafe {
-            XClearArea(
-                self.display,
-                self.window_id,
-                self.verse_x,
-                self.verse_y,
-                w as u32 + boundary,
-                h as u32 + boundary,
-                0_i32,
-            );
+            XCreateGC(
+                display,
+                window,
+                (GCForeground | GCGraphicsExposures) as u64,
+                &mut values,
+            )
         }
     }
 
-    pub fn draw_e4verse(&mut self) {
-        let step = 5;
-
-        let mut rng = rand::rng();
-        // Get a verse
-        let mut e4verse = BibleVerse::new(
-            self.line_length,
-            self.bible_path.clone(),
-        );
-        let original_verse = e4verse.verse.clone();
-
+    // A pixmap as large as the window, filled with the background
+    fn create_back_buffer(
+        display: *mut Display,
+        window: Window,
+        background_gc: GC,
+        width: i32,
+        height: i32,
+    ) -> Pixmap {
         let mut attrs = MaybeUninit::<XWindowAttributes>::uninit();
         unsafe {
-            XGetWindowAttributes(self.display, self.window_id, attrs.as_mut_ptr());
+            XGetWindowAttributes(display, window, attrs.as_mut_ptr());
         }
-        let attrs2 = unsafe { attrs.assume_init() };
-        let win_ref = attrs2.visual;
-        let screen_ptr = unsafe { XDefaultScreenOfDisplay(self.display) };
-        let colormap = unsafe { (*screen_ptr).cmap };
-
-        let draw = unsafe { XftDrawCreate(self.display, self.window_id, win_ref, colormap) };
-
-        let white = XftColor {
-            pixel: 0xFFFFFF, // Pixel value for white
-            color: XRenderColor {
-                red: 65535,
-                green: 65535,
-                blue: 65535,
-                alpha: 65535,
-            },
-        };
+        let depth = unsafe { attrs.assume_init() }.depth as u32;
+        let (width, height) = (width.max(1) as u32, height.max(1) as u32);
+        unsafe {
+            let pixmap = XCreatePixmap(display, window, width, height, depth);
+            XFillRectangle(display, pixmap, background_gc, 0, 0, width, height);
+            pixmap
+        }
+    }
+
+    // Handles the events received since the last frame: the uncovered areas are copied
+    // again from the back buffer, a new size gets a new back buffer and font size
+    fn handle_events(&mut self) -> WindowChange {
+        let mut change = WindowChange::Unchanged;
+        while unsafe { XPending(self.display) } > 0 {
+            let mut event: XEvent = unsafe { std::mem::zeroed() };
+            unsafe { XNextEvent(self.display, &mut event) };
+            match event.get_type() {
+                xlib::ConfigureNotify => {
+                    let configure = unsafe { event.configure };
+                    let resized = configure.width != self.width || configure.height != self.height;
+                    if resized {
+                        self.resize(configure.width, configure.height);
+                    }
+                    // A moved window may be on other monitors
+                    let monitors =
+                        monitors::query(self.display, self.window_id, self.width, self.height);
+                    if resized || monitors != self.monitors {
+                        self.monitors = monitors;
+                        change = WindowChange::Resized;
+                    }
+                }
+                xlib::Expose => {
+                    let expose = unsafe { event.expose };
+                    unsafe {
+                        XCopyArea(
+                            self.display,
+                            self.back_buffer,
+                            self.window_id,
+                            self.background_gc,
+                            expose.x,
+                            expose.y,
+                            expose.width as u32,
+                            expose.height as u32,
+                            expose.x,
+                            expose.y,
+                        );
+                    }
+                }
+                xlib::ClientMessage => {
+                    let message = unsafe { event.client_message };
+                    if self.wm_delete_window != 0
+                        && message.data.get_long(0) as Atom == self.wm_delete_window
+                    {
+                        return WindowChange::Closed;
+                    }
+                }
+                _ => {}
+            }
+        }
+        unsafe { XFlush(self.display) };
+
+        change
+    }
+
+    fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+        unsafe { XFreePixmap(self.display, self.back_buffer) };
+        self.back_buffer = Self::create_back_buffer(
+            self.display,
+            self.window_id,
+            self.background_gc,
+            width,
+            height,
+        );
+        // The new back buffer is empty: copy all of it with the next frame
+        self.drawn = Some((0, 0, width.max(1) as u32, height.max(1) as u32));
+    }
 
+    // Opens the fonts for the monitor size, shrunk until the passages fit the monitor
+    fn fit_fonts(&self, blocks: &[String], step: i32, monitor: Area) -> (Fonts, TextBlock) {
+        let (width, height) = (monitor.2 as i32, monitor.3 as i32);
         let screen_count = unsafe { XScreenCount(self.display) };
 
         if screen_count == 0 {
@@ -203,82 +462,360 @@ impl ScreensaverSetup {
         }
 
         let screen_num = 0;
-        let font_name = CString::new(format!("Sans-{}", self.font_size)).unwrap();
-        let xft_font = unsafe { XftFontOpenName(self.display, screen_num, font_name.as_ptr()) };
-        let mut extents = XGlyphInfo {
-            width: 0,
-            height: 0,
-            x: 0,
-            y: 0,
-            xOff: 0,
-            yOff: 0,
-        };
+        // Calculate the font size in percentual of the monitor size
+        let mut font_size = Self::calculate_font_size(width as f64, self.font_size as f64);
+        let mut fonts = self.open_fonts(screen_num, font_size);
+        let mut block = self.layout(fonts, blocks, width);
+
+        // Long passages may not fit the monitor: shrink the font until they do
+        while font_size > MIN_FONT_SIZE
+            && (block.width > width || (block.line_height + step * 2) * block.rows > height)
+        {
+            self.close_fonts(fonts);
+            font_size -= 1;
+            fonts = self.open_fonts(screen_num, font_size);
+            block = self.layout(fonts, blocks, width);
+        }
 
-        let mut text_width: i32 = 0;
-        let mut text_height: i32 = 0;
+        (fonts, block)
+    }
 
-        for line in original_verse.lines() {
+    // A new direction after bouncing in a corner
+    fn random_direction(&mut self) -> Direction {
+        Direction::from(self.animation_rng.random_range(0..=Direction::max()))
+    }
+
+    // Composes a frame in the back buffer and copies it to the window in one operation:
+    // the text of the previous frame is cleared and the text is drawn in its new area,
+    // given in window coordinates
+    fn render_frame(
+        &mut self,
+        draw: *mut XftDraw,
+        fonts: Fonts,
+        block: &TextBlock,
+        text_height: i32,
+        area: Area,
+    ) {
+        if let Some((x, y, width, height)) = self.drawn {
             unsafe {
-                XftTextExtentsUtf8(
+                XFillRectangle(
                     self.display,
-                    xft_font,
-                    line.as_ptr() as *const _,
-                    line.len() as i32,
-                    &mut extents,
+                    self.back_buffer,
+                    self.background_gc,
+                    x,
+                    y,
+                    width,
+                    height,
                 )
             };
+        }
+
+        for line in &block.lines {
+            let x = area.0 + line.x;
+            let y = area.1 + text_height * (line.row + 1);
+            unsafe {
+                XftDrawStringUtf8(
+                    draw,
+                    &self.colors.reference,
+                    fonts.reference,
+                    x,
+                    y,
+                    line.reference.as_ptr(),
+                    line.reference.len() as i32,
+                );
+                XftDrawStringUtf8(
+                    draw,
+                    &self.colors.foreground,
+                    fonts.text,
+                    x + line.reference_width,
+                    y,
+                    line.text.as_ptr(),
+                    line.text.len() as i32,
+                );
+            }
+        }
+
+        // Only the area which changed is copied
+        let (x, y, width, height) = match self.drawn {
+            Some(drawn) => union(drawn, area),
+            None => area,
+        };
+        unsafe {
+            XCopyArea(
+                self.display,
+                self.back_buffer,
+                self.window_id,
+                self.background_gc,
+                x,
+                y,
+                width,
+                height,
+                x,
+                y,
+            );
+            XFlush(self.display);
+        }
+        self.drawn = Some(area);
+    }
+
+    // Opens a font pattern at the size in points, or the default font if it cannot be opened
+    fn open_font(
+        display: *mut Display,
+        screen_num: i32,
+        pattern: &str,
+        font_size: i32,
+    ) -> *mut XftFont {
+        for pattern in [pattern, DEFAULT_FONT] {
+            let Ok(font_name) = CString::new(format!("{}:size={}", pattern, font_size)) else {
+                continue;
+            };
+            let xft_font = unsafe { XftFontOpenName(display, screen_num, font_name.as_ptr()) };
+            if !xft_font.is_null() {
+                return xft_font;
+            }
+        }
+
+        panic!("No font found for {}.", pattern);
+    }
+
+    // The pattern if the font can be opened, the default font otherwise
+    fn checked_font(display: *mut Display, pattern: String) -> String {
+        let font_name = CString::new(pattern.as_str()).unwrap_or_default();
+        let xft_font = unsafe { XftFontOpenName(display, 0, font_name.as_ptr()) };
+        if xft_font.is_null() {
+            eprintln!("cannot open font '{}', using {}", pattern, DEFAULT_FONT);
+            return DEFAULT_FONT.to_string();
+        }
+        unsafe { XftFontClose(display, xft_font) };
+        pattern
+    }
+
+    fn open_fonts(&self, screen_num: i32, font_size: i32) -> Fonts {
+        Fonts {
+            text: Self::open_font(self.display, screen_num, &self.font, font_size),
+            reference: Self::open_font(self.display, screen_num, &self.reference_font, font_size),
+        }
+    }
+
+    fn close_fonts(&self, fonts: Fonts) {
+        unsafe {
+            XftFontClose(self.display, fonts.text);
+            XftFontClose(self.display, fonts.reference);
+        }
+    }
+
+    // Places the lines of the passages stacked or side by side
+    fn layout(&self, fonts: Fonts, blocks: &[String], width: i32) -> TextBlock {
+        // Space between the translations placed side by side
+        let column_gap = (width as f64 / 40.0_f64).round() as i32;
+
+        let mut placed = Vec::new();
+        let mut block_width = 0;
+        let mut line_height = 0;
+        let mut rows = 0;
+        let mut x = 0;
+        for (i, block) in blocks.iter().enumerate() {
+            let (left, top) = match self.layout {
+                Layout::Stacked => {
+                    if i > 0 {
+                        // An empty line between the translations
+                        rows += 1;
+                    }
+                    (0, rows)
+                }
+                Layout::SideBySide => {
+                    if i > 0 {
+                        x += column_gap;
+                    }
+                    (x, 0)
+                }
+            };
 
-            let width = extents.width as i32;
-            if width > text_width {
-                text_width = width;
+            let mut width = 0;
+            let mut in_reference = true;
+            for (row, line) in block.lines().enumerate() {
+                let (line, line_width, height) =
+                    self.place(fonts, left, top + row as i32, line, &mut in_reference);
+                width = width.max(line_width);
+                line_height = line_height.max(height);
+                placed.push(line);
             }
 
-            let height = extents.height as i32;
-            if height > text_height {
-                text_height = height;
+            let count = block.lines().count() as i32;
+            match self.layout {
+                Layout::Stacked => {
+                    rows += count;
+                    block_width = block_width.max(width);
+                }
+                Layout::SideBySide => {
+                    rows = rows.max(count);
+                    x += width;
+                    block_width = x;
+                }
             }
         }
-        text_height += step;
 
-        let verse_height = (text_height + step) * original_verse.lines().count() as i32;
+        TextBlock {
+            lines: placed,
+            width: block_width,
+            line_height,
+            rows,
+        }
+    }
+
+    // Splits the reference, e.g. `[Giovanni 3:16]`, from the text: a passage starts with it,
+    // and it may be wrapped on more lines. Returns the line with its width and height
+    fn place(
+        &self,
+        fonts: Fonts,
+        x: i32,
+        row: i32,
+        line: &str,
+        in_reference: &mut bool,
+    ) -> (Line, i32, i32) {
+        let mut split = 0;
+        if *in_reference {
+            split = match line.find(']') {
+                Some(end) => {
+                    *in_reference = false;
+                    end + 1
+                }
+                None => line.len(),
+            };
+        }
+        let (reference, text) = line.split_at(split);
+
+        let reference_extents = self.measure(fonts.reference, reference);
+        let text_extents = self.measure(fonts.text, text);
+        // The text starts where the reference ends
+        let reference_width = reference_extents.xOff as i32;
+        let width = reference_width + text_extents.width as i32;
+        let height = reference_extents.height.max(text_extents.height) as i32;
+
+        let line = Line {
+            x,
+            row,
+            reference: reference.to_string(),
+            reference_width,
+            text: text.to_string(),
+        };
+        (line, width, height)
+    }
+
+    fn measure(&self, xft_font: *mut XftFont, text: &str) -> XGlyphInfo {
+        let mut extents: XGlyphInfo = unsafe { std::mem::zeroed() };
+        unsafe {
+            XftTextExtentsUtf8(
+                self.display,
+                xft_font,
+                text.as_ptr(),
+                text.len() as i32,
+                &mut extents,
+            )
+        };
+        extents
+    }
+
+    /// Shows a verse for the duration, returns false when the window has been closed
+    pub fn draw_e4verse(&mut self) -> bool {
+        let step = 5;
+
+        // Get a verse
+        let mut e4verse = match BibleVerse::select(
+            &mut self.rng,
+            self.source.as_ref(),
+            &mut self.history,
+            &self.selection,
+        ) {
+            Ok(e4verse) => e4verse.with_translations(&self.translations),
+            Err(e) => {
+                // Keep the screen blank for a while instead of retrying in a tight loop
+                eprintln!("{}", e);
+                std::thread::sleep(std::time::Duration::from_secs(self.duration));
+                return self.handle_events() != WindowChange::Closed;
+            }
+        };
+        // Each translation is wrapped on its own
+        let blocks: Vec<String> = e4verse
+            .passages()
+            .iter()
+            .map(|passage| verse::wrap(&passage.to_string(), self.line_length))
+            .collect();
+
+        // The text is drawn in the back buffer, never directly in the window
+        let draw =
+            unsafe { XftDrawCreate(self.display, self.back_buffer, self.visual, self.colormap) };
+
+        // The verse is kept on a single monitor, the positions are relative to it
+        let mut index = match self.monitors.len() {
+            1 => 0,
+            n => self.animation_rng.random_range(0..n),
+        };
+        let mut monitor = self.monitors[index];
+        let (mut width, mut height) = (monitor.2 as i32, monitor.3 as i32);
+
+        let (mut fonts, mut block) = self.fit_fonts(&blocks, step, monitor);
+        let mut text_width = block.width;
+        let mut text_height = block.line_height + step;
+
+        let mut verse_height = (text_height + step) * block.rows;
 
         let frame_interval = std::time::Duration::from_millis(FPS);
+        // Boundary, in pixels, added to the area of the text
+        let mut boundary: u32 = (width as f64 / 40.0_f64).round() as u32;
 
-        self.verse_x = rng.random_range(0..text_width);
-        self.verse_y = rng.random_range(0..verse_height);
+        // Anywhere the whole verse is on the monitor
+        self.verse_x = self.animation_rng.random_range(0..=(width - text_width).max(0));
+        self.verse_y = self.animation_rng.random_range(0..=(height - verse_height).max(0));
         let now = std::time::SystemTime::now();
 
         //while self.x > (text_width * -1) {
         while now.elapsed().unwrap().as_secs() < self.duration {
-            // Write text to screen
-            let mut i = 0;
-            for line in original_verse.lines() {
-                i += 1;
-                unsafe {
-                    XftDrawStringUtf8(
-                        draw,
-                        &white,
-                        xft_font,
-                        self.verse_x,
-                        self.verse_y + text_height * i,
-                        line.as_ptr(),
-                        line.len() as i32,
-                    )
-                };
-            }
-            // Flush everything
-            unsafe { XFlush(self.display) };
+            let area = (
+                monitor.0 + self.verse_x,
+                monitor.1 + self.verse_y,
+                (text_width + step) as u32 + boundary,
+                verse_height as u32 + boundary,
+            );
+            self.render_frame(draw, fonts, &block, text_height, area);
             std::thread::sleep(frame_interval);
-            self.clear(text_width + step, verse_height);
+
+            match self.handle_events() {
+                WindowChange::Unchanged => {}
+                WindowChange::Resized => {
+                    // Same verse, laid out again for the new size
+                    unsafe {
+                        XftDrawChange(draw, self.back_buffer);
+                    }
+                    self.close_fonts(fonts);
+                    // The monitors may have changed too
+                    index = index.min(self.monitors.len() - 1);
+                    monitor = self.monitors[index];
+                    (width, height) = (monitor.2 as i32, monitor.3 as i32);
+                    (fonts, block) = self.fit_fonts(&blocks, step, monitor);
+                    text_width = block.width;
+                    text_height = block.line_height + step;
+                    verse_height = (text_height + step) * block.rows;
+                    boundary = (width as f64 / 40.0_f64).round() as u32;
+                    self.verse_x = self.verse_x.min(width - text_width).max(0);
+                    self.verse_y = self.verse_y.min(height - verse_height).max(0);
+                    continue;
+                }
+                WindowChange::Closed => {
+                    unsafe {
+                        XftDrawDestroy(draw);
+                    }
+                    self.close_fonts(fonts);
+                    return false;
+                }
+            }
             match e4verse.direction {
                 crate::bibleverse::Direction::NorthWest => {
                     self.verse_x -= FLUTTUATION_SIZE;
                     self.verse_y -= FLUTTUATION_SIZE;
-                    let index = rand::rng().random_range(0..=Direction::max());
-                    let direction = Direction::from(index);
 
                     if self.verse_x < 0 && self.verse_y < 0 {
-                        e4verse.direction = direction;
+                        e4verse.direction = self.random_direction();
                         self.verse_x = 0;
                         self.verse_y = 0;
                     } else if self.verse_x < 0 {
@@ -292,15 +829,13 @@ impl ScreensaverSetup {
                 crate::bibleverse::Direction::NorthEeast => {
                     self.verse_x += FLUTTUATION_SIZE;
                     self.verse_y -= FLUTTUATION_SIZE;
-                    let index = rand::rng().random_range(0..=Direction::max());
-                    let direction = Direction::from(index);
 
-                    if (self.verse_x + text_width) > self.width && self.verse_y < 0 {
-                        self.verse_x = self.width - text_width;
+                    if (self.verse_x + text_width) > width && self.verse_y < 0 {
+                        self.verse_x = width - text_width;
                         self.verse_y = 0;
-                        e4verse.direction = direction;
-                    } else if (self.verse_x + text_width) > self.width {
-                        self.verse_x = self.width - text_width;
+                        e4verse.direction = self.random_direction();
+                    } else if (self.verse_x + text_width) > width {
+                        self.verse_x = width - text_width;
                         e4verse.direction = crate::bibleverse::Direction::NorthWest;
                     } else if self.verse_y < 0 {
                         self.verse_y = 0;
@@ -310,40 +845,64 @@ impl ScreensaverSetup {
                 crate::bibleverse::Direction::SouthEeast => {
                     self.verse_x += FLUTTUATION_SIZE;
                     self.verse_y += FLUTTUATION_SIZE;
-                    let index = rand::rng().random_range(0..=Direction::max());
-                    let direction = Direction::from(index);
-
-                    if (self.verse_x + text_width) > self.width && (self.verse_y + verse_height) > self.height {
-                        self.verse_x = self.width - text_height;
-                        self.verse_y = self.height - verse_height;
-                        e4verse.direction = direction;
-                    } else if (self.verse_x + text_width) > self.width {
-                        self.verse_x = self.width - text_width;
+
+                    if (self.verse_x + text_width) > width && (self.verse_y + verse_height) > height {
+                        self.verse_x = width - text_height;
+                        self.verse_y = height - verse_height;
+                        e4verse.direction = self.random_direction();
+                    } else if (self.verse_x + text_width) > width {
+                        self.verse_x = width - text_width;
                         e4verse.direction = crate::bibleverse::Direction::SouthWest;
-                    } else if (self.verse_y + verse_height) > self.height {
-                        self.verse_y = self.height - verse_height;
+                    } else if (self.verse_y + verse_height) > height {
+                        self.verse_y = height - verse_height;
                         e4verse.direction = crate::bibleverse::Direction::NorthEeast;
                     }
                 }
                 crate::bibleverse::Direction::SouthWest => {
                     self.verse_x -= FLUTTUATION_SIZE;
                     self.verse_y += FLUTTUATION_SIZE;
-                    let index = rand::rng().random_range(0..=Direction::max());
-                    let direction = Direction::from(index);
 
-                    if self.verse_x < 0 && (self.verse_y + verse_height) > self.height {
+                    if self.verse_x < 0 && (self.verse_y + verse_height) > height {
                         self.verse_x = 0;
-                        self.verse_y = self.height - verse_height;
-                        e4verse.direction = direction;
+                        self.verse_y = height - verse_height;
+                        e4verse.direction = self.random_direction();
                     } else if self.verse_x < 0 {
                         self.verse_x = 0;
                         e4verse.direction = crate::bibleverse::Direction::SouthEeast;
-                    } else if (self.verse_y + verse_height) > self.height {
-                        self.verse_y = self.height - verse_height;
+                    } else if (self.verse_y + verse_height) > height {
+                        self.verse_y = height - verse_height;
                         e4verse.direction = crate::bibleverse::Direction::NorthWest;
                     }
                 }
             }
         }
+
+        unsafe {
+            XftDrawDestroy(draw);
+        }
+        self.close_fonts(fonts);
+
+        true
     }
 }
+
+impl Drop for ScreensaverSetup {
+    fn drop(&mut self) {
+        unsafe {
+            XFreePixmap(self.display, self.back_buffer);
+            XFreeGC(self.display, self.background_gc);
+            self.colors.free(self.display, self.visual, self.colormap);
+            // Destroys the window created by this program too
+            XCloseDisplay(self.display);
+        }
+    }
+}
+
+// The smallest area containing both areas
+fn union(a: Area, b: Area) -> Area {
+    let x = a.0.min(b.0);
+    let y = a.1.min(b.1);
+    let right = (a.0 + a.2 as i32).max(b.0 + b.2 as i32);
+    let bottom = (a.1 + a.3 as i32).max(b.1 + b.3 as i32);
+    (x, y, (right - x) as u32, (bottom - y) as u32)
+}
@@ -1,22 +1,55 @@
+use crate::books::book_key;
+use crate::favorites::Favorites;
+use crate::reference::Reference;
+use crate::translation::Translation;
+use crate::verse::{Passage, Verse, VerseError};
+use crate::versefilter::VerseFilter;
+use crate::versehistory::VerseHistory;
+use crate::versematcher::VerseMatcher;
+use crate::versesource::VerseSource;
+use std::collections::HashMap;
 use rand::{
     Rng,
 };
-use std::io::Error;
 
-mod versereader {
+// How many random positions are read before falling back to a full scan
+const MAX_INDEX_ATTEMPTS: usize = 64;
+
+pub mod versereader {
+    use flate2::bufread::MultiGzDecoder;
     use std::{
         fs::File,
         io::{self, prelude::*},
     };
+    use xz2::bufread::XzDecoder;
+
+    // Magic bytes at the start of the compressed files
+    const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+    const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+    const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
 
+    /// Reads a file line by line, decompressing it on the fly
+    /// when it is compressed with gzip, zstd or xz
     pub struct BufReader {
-        reader: io::BufReader<File>,
+        reader: Box<dyn BufRead>,
     }
 
     impl BufReader {
         pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
             let file = File::open(path)?;
-            let reader = io::BufReader::new(file);
+            let mut file = io::BufReader::new(file);
+
+            // The compression is recognized from the first bytes, without consuming them
+            let head = file.fill_buf()?;
+            let reader: Box<dyn BufRead> = if head.starts_with(GZIP_MAGIC) {
+                Box::new(io::BufReader::new(MultiGzDecoder::new(file)))
+            } else if head.starts_with(ZSTD_MAGIC) {
+                Box::new(io::BufReader::new(zstd::Decoder::with_buffer(file)?))
+            } else if head.starts_with(XZ_MAGIC) {
+                Box::new(io::BufReader::new(XzDecoder::new_multi_decoder(file)))
+            } else {
+                Box::new(file)
+            };
 
             Ok(Self { reader })
         }
@@ -33,14 +66,68 @@ mod versereader {
                 .transpose()
         }
     }
+
+    impl Read for BufReader {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            self.reader.read(buffer)
+        }
+    }
+
+    impl BufRead for BufReader {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            self.reader.fill_buf()
+        }
+
+        fn consume(&mut self, amount: usize) {
+            self.reader.consume(amount)
+        }
+    }
+
+    /// True if the file is compressed, so it can not be read at random positions
+    pub fn is_compressed(path: impl AsRef<std::path::Path>) -> io::Result<bool> {
+        let mut head = Vec::new();
+        File::open(path)?.take(XZ_MAGIC.len() as u64).read_to_end(&mut head)?;
+
+        Ok([GZIP_MAGIC, ZSTD_MAGIC, XZ_MAGIC]
+            .iter()
+            .any(|magic| head.starts_with(magic)))
+    }
 }
 
 #[derive(Debug)]
 pub struct BibleVerse {
-    pub verse: String,
+    pub passage: Passage,
+    // The same passage in the other translations, if they have it
+    pub parallel: Vec<Passage>,
     pub direction: Direction,
 }
 
+/// How the verses are chosen
+#[derive(Debug, Clone)]
+pub struct Selection {
+    // The passage to show every time, instead of a random one
+    pub reference: Option<Reference>,
+    // Passages shown instead of a random verse, with their probability
+    pub favorites: Option<Favorites>,
+    // The salt of the verse of the day, None to show random verses
+    pub verse_of_the_day: Option<String>,
+    pub filter: VerseFilter,
+    // Only the verses matching a topic or a pattern are chosen, the passage may extend beyond them
+    pub matcher: Option<VerseMatcher>,
+    // Number of consecutive verses shown together
+    pub passage_length: usize,
+    // Show the whole chapter when it has at most this number of verses, 0 to disable
+    pub whole_chapter_max: usize,
+}
+
+#[derive(Debug)]
+pub enum BibleVerseError {
+    Io(std::io::Error),
+    Parse(VerseError),
+    // The file does not contain any verse
+    NoVerses,
+}
+
 #[derive(Debug)]
 pub enum Direction {
     NorthWest,
@@ -68,84 +155,487 @@ impl From<i8> for Direction {
     }
 }
 
+impl Selection {
+    /// True if the verse can be chosen
+    pub fn accepts(&self, verse: &Verse) -> bool {
+        self.filter.accepts(verse)
+            && self.matcher.as_ref().is_none_or(|matcher| matcher.is_match(&verse.text))
+    }
+
+    /// True if every verse can be chosen
+    pub fn accepts_all(&self) -> bool {
+        self.filter.is_empty() && self.matcher.is_none()
+    }
+}
+
 impl BibleVerse {
-    pub fn new(line_length: i32, bible_path: String) -> Self {
-        let v = Self::new_verse(line_length, bible_path).unwrap();
+    /// Chooses a verse as requested by the selection
+    pub fn select<R: Rng + ?Sized>(
+        rng: &mut R,
+        source: &dyn VerseSource,
+        history: &mut VerseHistory,
+        selection: &Selection,
+    ) -> Result<Self, BibleVerseError> {
+        if let Some(favorites) = &selection.favorites {
+            if selection.reference.is_none() && rng.random_bool(favorites.ratio) {
+                let passage = favorites.choose(rng).clone();
+                return Ok(Self::of_passage(rng, passage));
+            }
+        }
+
+        match (&selection.reference, &selection.verse_of_the_day) {
+            (Some(reference), _) => Self::of_reference(rng, source, reference),
+            (None, Some(salt)) => Self::of_the_day(rng, source, selection, salt),
+            (None, None) => Self::new(rng, source, history, selection),
+        }
+    }
+
+    pub fn new<R: Rng + ?Sized>(
+        rng: &mut R,
+        source: &dyn VerseSource,
+        history: &mut VerseHistory,
+        selection: &Selection,
+    ) -> Result<Self, BibleVerseError> {
+        let accept = |v: &Verse| selection.accepts(v) && !history.contains(v);
+        let (v, position) = match Self::new_verse(rng, source, &accept)? {
+            Some(chosen) => chosen,
+            None => {
+                // Every verse has already been shown: start a new cycle
+                history.clear();
+                Self::new_verse(rng, source, &|v| selection.accepts(v))?
+                    .ok_or(BibleVerseError::NoVerses)?
+            }
+        };
+
+        let passage = Self::passage(source, v, position, selection)?;
+        history.push_all(&passage.verses);
 
-        let index = rand::rng().random_range(0..=Direction::max());
+        let index = rng.random_range(0..=Direction::max());
         let direction = Direction::from(index);
-        
+
+        Ok(Self {
+            passage,
+            parallel: Vec::new(),
+            direction,
+        })
+    }
+
+    /// The verse of the current local day: every run with the same bible file
+    /// and salt shows the same verse until midnight
+    pub fn of_the_day<R: Rng + ?Sized>(
+        rng: &mut R,
+        source: &dyn VerseSource,
+        selection: &Selection,
+        salt: &str,
+    ) -> Result<Self, BibleVerseError> {
+        let seed = Self::stable_hash(&format!("{}|{}", Self::today(), salt));
+        let (v, position) = Self::nth_verse(source, selection, seed)?;
+        let passage = Self::passage(source, v, position, selection)?;
+
+        let index = rng.random_range(0..=Direction::max());
+        let direction = Direction::from(index);
+
+        Ok(Self {
+            passage,
+            parallel: Vec::new(),
+            direction,
+        })
+    }
+
+    /// The passage of a reference, e.g. `Sal 23:1-4,6`
+    pub fn of_reference<R: Rng + ?Sized>(
+        rng: &mut R,
+        source: &dyn VerseSource,
+        reference: &Reference,
+    ) -> Result<Self, BibleVerseError> {
+        Ok(Self::of_passage(rng, Self::referenced(source, reference)?))
+    }
+
+    fn of_passage<R: Rng + ?Sized>(rng: &mut R, passage: Passage) -> Self {
+        let index = rng.random_range(0..=Direction::max());
+        let direction = Direction::from(index);
+
         Self {
-            verse: v,
+            passage,
+            parallel: Vec::new(),
             direction,
         }
     }
 
-    // Gets a random line from a file and returns as a string
-    fn get_random_line(bible_path: &String) -> String {
-        // Create a file buffer reader
-        let mut reader = versereader::BufReader::open(bible_path).unwrap();
+    /// The verses of a reference, in the order of the source
+    pub fn referenced(
+        source: &dyn VerseSource,
+        reference: &Reference,
+    ) -> Result<Passage, BibleVerseError> {
+        let mut verses = Vec::new();
+        // Whether each book name is the referenced book, they are the same for many verses
+        let mut books: HashMap<String, bool> = HashMap::new();
+        source.scan(&mut |v| {
+            if let Ok(v) = v {
+                let same_book = *books
+                    .entry(v.book.clone())
+                    .or_insert_with(|| book_key(&v.book) == reference.book_key());
+                if same_book && reference.contains_position(v.chapter, v.verse) {
+                    verses.push(v);
+                }
+            }
+        })?;
 
-        // Initializes the reservoir with a random line from the file
-        let mut reservoir: Vec<String> = Vec::new();
-        let mut buffer = String::new();
-        if let Some(line) = reader.read_line(&mut buffer) {
-            reservoir.push(line.unwrap().to_string());
+        if verses.is_empty() {
+            return Err(BibleVerseError::NoVerses);
         }
 
-        // Iterates each line of the file file and replaces one line of the reservoir with probability 1/n
-        let mut n = 1;
-        while let Some(line) = reader.read_line(&mut buffer) {
-            n += 1;
-            let random_index = (rand::random::<i8>() as usize) % n;
-            if random_index < reservoir.len() {
-                reservoir[random_index] = line.unwrap().to_string();
+        Ok(Passage { verses })
+    }
+
+    /// Adds the same passage from other translations, skipping the ones which do not have it
+    pub fn with_translations(mut self, translations: &[Translation]) -> Self {
+        self.parallel = translations
+            .iter()
+            .map(|translation| translation.align(&self.passage))
+            .filter(|passage| !passage.is_empty())
+            .collect();
+        self
+    }
+
+    /// The main passage followed by its translations, one per item
+    pub fn passages(&self) -> Vec<&Passage> {
+        std::iter::once(&self.passage).chain(&self.parallel).collect()
+    }
+
+    // Extends the starting verse to the passage requested by the selection:
+    // the whole chapter when it is short enough, otherwise the following verses
+    fn passage(
+        source: &dyn VerseSource,
+        start: Verse,
+        position: Option<usize>,
+        selection: &Selection,
+    ) -> Result<Passage, BibleVerseError> {
+        if selection.passage_length <= 1 && selection.whole_chapter_max == 0 {
+            return Ok(Passage { verses: vec![start] });
+        }
+
+        let chapter: Vec<Verse> = Self::chapter(source, &start, position)?
+            .into_iter()
+            .filter(|v| selection.filter.accepts(v))
+            .collect();
+        if chapter.len() <= selection.whole_chapter_max {
+            return Ok(Passage { verses: chapter });
+        }
+
+        let from = chapter
+            .iter()
+            .position(|v| v.verse == start.verse)
+            .unwrap_or_default();
+        let passage: Vec<Verse> = chapter
+            .into_iter()
+            .skip(from)
+            .take(selection.passage_length.max(1))
+            .collect();
+        if passage.is_empty() {
+            return Ok(Passage { verses: vec![start] });
+        }
+
+        Ok(Passage { verses: passage })
+    }
+
+    // All the verses of the chapter of a verse, in the order of the source. With the
+    // position of the verse only the nearby verses are read, otherwise the source is scanned
+    fn chapter(
+        source: &dyn VerseSource,
+        verse: &Verse,
+        position: Option<usize>,
+    ) -> Result<Vec<Verse>, BibleVerseError> {
+        let same_chapter = |v: &Verse| v.book == verse.book && v.chapter == verse.chapter;
+
+        if let (Some(len), Some(position)) = (source.len(), position) {
+            // Malformed lines are stepped over, as the scan does: the chapter ends
+            // at a verse of another chapter, or if the source changed
+            let read = |n: usize| match source.get(n)? {
+                Ok(v) => same_chapter(&v).then_some(Some(v)),
+                Err(_) => Some(None),
+            };
+
+            let mut before = Vec::new();
+            let mut n = position;
+            while n > 0 {
+                n -= 1;
+                match read(n) {
+                    Some(v) => before.extend(v),
+                    None => break,
+                }
+            }
+            before.reverse();
+
+            let mut after = Vec::new();
+            let mut n = position;
+            while n < len {
+                match read(n) {
+                    Some(v) => after.extend(v),
+                    None => break,
+                }
+                n += 1;
+            }
+
+            // Make sure the source did not change while reading
+            if !after.is_empty() {
+                before.extend(after);
+                return Ok(before);
             }
         }
 
-        // Returns one random line from the reservoir
-        let random_index = (rand::random::<i8>() as usize) % reservoir.len();
-        reservoir[random_index].clone()
-    }
-
-    fn new_verse(line_length: i32, bible_path: String) -> Result<String, Error> {
-        const BIBLE_SEPARATOR: &str = "|";
-
-        //Select a random verse
-        let verse = Self::get_random_line(&bible_path);
-
-        let fields: Vec<&str> = verse.split(BIBLE_SEPARATOR).collect();
-        //fields[0] = book name; fields[1] = chapter number; fields[2] = verse number; fields[3] = verse text
-        let mut formatted_verse: String = format!(
-            "[{} {}:{}] {}",
-            &(fields[0]).trim(),
-            &(fields[1]).trim(),
-            &(fields[2]).trim(),
-            &(fields[3]).trim()
-        );
-        //Format the verse to max max_verse_line_len characters
-        //by adding \n
-        let cloned_verse = formatted_verse.clone();
-        let mut i: i32 = 0;
-        formatted_verse = String::from("");
-        for word in cloned_verse.split_whitespace() {
-            let count: i32 = word.chars().count().try_into().unwrap();
-            if (i + count) > line_length {
-                formatted_verse.push('\n');
-                i = 0;
-            } else {
-                i += count;
+        let mut chapter = Vec::new();
+        source.scan(&mut |v| {
+            if let Ok(v) = v {
+                if same_chapter(&v) {
+                    chapter.push(v);
+                }
+            }
+        })?;
+
+        Ok(chapter)
+    }
+
+    // Returns the verse at the `n`-th position among the ones accepted by the selection
+    // (modulo their number), or the first valid verse after it when that one is malformed
+    fn nth_verse(
+        source: &dyn VerseSource,
+        selection: &Selection,
+        n: u64,
+    ) -> Result<(Verse, Option<usize>), BibleVerseError> {
+        // Without a filter every position is accepted, no need to scan
+        if let Some(count) = source.len().filter(|len| *len > 0 && selection.accepts_all()) {
+            let count = count as u64;
+            for k in 0..count {
+                let position = ((n % count + k) % count) as usize;
+                match source.get(position) {
+                    Some(Ok(v)) => return Ok((v, Some(position))),
+                    Some(Err(_)) => {}
+                    // The source changed
+                    None => break,
+                }
+            }
+        }
+
+        // Same selection with a scan: keep the accepted verses in memory.
+        // Malformed verses are kept only without a filter, as the positions do
+        let mut verses = Vec::new();
+        source.scan(&mut |v| match v {
+            Ok(v) if !selection.accepts(&v) => {}
+            Err(_) if !selection.accepts_all() => {}
+            v => verses.push(v),
+        })?;
+
+        let count = verses.len() as u64;
+        let mut first_error = None;
+        for k in 0..count {
+            match &verses[((n % count + k) % count) as usize] {
+                Ok(v) => return Ok((v.clone(), None)),
+                Err(e) => {
+                    first_error.get_or_insert(e.clone());
+                }
+            }
+        }
+
+        Err(first_error.map_or(BibleVerseError::NoVerses, BibleVerseError::Parse))
+    }
+
+    // The current local date as YYYY-MM-DD
+    fn today() -> String {
+        let mut tm = std::mem::MaybeUninit::<libc::tm>::uninit();
+        let tm = unsafe {
+            let now = libc::time(std::ptr::null_mut());
+            libc::localtime_r(&now, tm.as_mut_ptr());
+            tm.assume_init()
+        };
+
+        format!("{:04}-{:02}-{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday)
+    }
+
+    // 64 bit FNV-1a: unlike the std hashers, it gives the same result on every machine and version
+    fn stable_hash(text: &str) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in text.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+
+    // Selects a random verse among the ones accepted by the filter, skipping malformed lines,
+    // and returns it with its position. Reads random positions directly when the source
+    // allows it, and falls back to a full scan when it does not or most verses are filtered out
+    fn new_verse<R: Rng + ?Sized>(
+        rng: &mut R,
+        source: &dyn VerseSource,
+        accept: &dyn Fn(&Verse) -> bool,
+    ) -> Result<Option<(Verse, Option<usize>)>, BibleVerseError> {
+        if let Some(len) = source.len().filter(|len| *len > 0) {
+            for _ in 0..MAX_INDEX_ATTEMPTS {
+                let n = rng.random_range(0..len);
+                match source.get(n) {
+                    Some(Ok(v)) => {
+                        if accept(&v) {
+                            return Ok(Some((v, Some(n))));
+                        }
+                    }
+                    Some(Err(_)) => {}
+                    // The source changed
+                    None => break,
+                }
+            }
+        }
+
+        Ok(Self::scan_random_verse(rng, source, accept)?.map(|v| (v, None)))
+    }
+
+    // Gets a random verse with a full scan of the source.
+    // The n-th accepted verse replaces the current choice with probability 1/n,
+    // so at the end every accepted verse has been chosen with the same probability
+    fn scan_random_verse<R: Rng + ?Sized>(
+        rng: &mut R,
+        source: &dyn VerseSource,
+        accept: &dyn Fn(&Verse) -> bool,
+    ) -> Result<Option<Verse>, BibleVerseError> {
+        let mut chosen = None;
+        let mut first_error = None;
+        let mut valid = false;
+        let mut n: usize = 0;
+        source.scan(&mut |v| {
+            let v = match v {
+                Ok(v) => v,
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                    return;
+                }
+            };
+            valid = true;
+            if !accept(&v) {
+                return;
+            }
+
+            n += 1;
+            if rng.random_range(0..n) == 0 {
+                chosen = Some(v);
             }
-            formatted_verse.push_str(word);
-            formatted_verse.push(' ');
+        })?;
+
+        // Report the parsing error only if there is nothing else to show
+        match first_error {
+            Some(e) if !valid => Err(BibleVerseError::Parse(e)),
+            _ => Ok(chosen),
+        }
+    }
+}
+
+impl std::fmt::Display for BibleVerseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BibleVerseError::Io(e) => write!(f, "{}", e),
+            BibleVerseError::Parse(e) => write!(f, "{}", e),
+            BibleVerseError::NoVerses => write!(f, "no verses found matching the selection"),
         }
+    }
+}
 
-        Ok(formatted_verse)
+impl std::error::Error for BibleVerseError {}
+
+impl From<std::io::Error> for BibleVerseError {
+    fn from(e: std::io::Error) -> Self {
+        BibleVerseError::Io(e)
+    }
+}
+
+impl From<VerseError> for BibleVerseError {
+    fn from(e: VerseError) -> Self {
+        BibleVerseError::Parse(e)
     }
 }
 
 impl std::fmt::Display for BibleVerse {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", &self.verse)
+        let passages: Vec<String> = self.passages().iter().map(|p| p.to_string()).collect();
+        write!(f, "{}", passages.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::versesource::TextSource;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{fs, path::PathBuf};
+
+    // Verses of the synthetic bible
+    const LINES: usize = 40;
+
+    // Chi-square with LINES - 1 = 39 degrees of freedom exceeded with probability 0.001
+    const CHI_SQUARE_CRITICAL: f64 = 72.05;
+
+    // A `book|chapter|verse|text` file with one verse per line, Genesi 1:1 to 1:LINES
+    fn synthetic_bible(name: &str) -> PathBuf {
+        let name = format!("e4biblesaver-{}-{}.txt", name, std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let text: String = (1..=LINES)
+            .map(|verse| format!("Genesi|1|{}|Testo del versetto {}\n", verse, verse))
+            .collect();
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    // Draws `samples` verses and checks the count of every line against the uniform expectation
+    fn assert_uniform(source: &dyn VerseSource, samples: usize) {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut counts = [0usize; LINES];
+        for _ in 0..samples {
+            let (verse, _) = BibleVerse::new_verse(&mut rng, source, &|_| true)
+                .unwrap()
+                .unwrap();
+            counts[verse.verse as usize - 1] += 1;
+        }
+
+        let expected = samples as f64 / LINES as f64;
+        // Standard deviation of the count of a single line
+        let deviation = (expected * (1.0 - 1.0 / LINES as f64)).sqrt();
+        for (line, count) in counts.iter().enumerate() {
+            assert!(
+                (*count as f64 - expected).abs() < 5.0 * deviation,
+                "line {} drawn {} times, {} expected",
+                line + 1,
+                count,
+                expected
+            );
+        }
+
+        let chi_square: f64 = counts
+            .iter()
+            .map(|count| (*count as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_square < CHI_SQUARE_CRITICAL, "chi-square {}", chi_square);
+    }
+
+    #[test]
+    fn index_selection_is_uniform() {
+        let path = synthetic_bible("index");
+        let source = TextSource::open(path.to_str().unwrap());
+        assert_eq!(source.len(), Some(LINES));
+
+        assert_uniform(&source, 20_000);
+
+        let mut index_path = path.clone().into_os_string();
+        index_path.push(".idx");
+        let _ = fs::remove_file(index_path);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn scan_selection_is_uniform() {
+        let path = synthetic_bible("scan");
+        let source = TextSource::without_index(path.to_str().unwrap());
+        assert_eq!(source.len(), None);
+
+        assert_uniform(&source, 8_000);
+
+        fs::remove_file(path).unwrap();
     }
 }
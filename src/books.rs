@@ -0,0 +1,199 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Testament {
+    Old,
+    New,
+}
+
+/// A book of the bible with the names it can be referred to
+#[derive(Debug)]
+pub struct Book {
+    /// USFM book code, e.g. `GEN`
+    pub id: &'static str,
+    /// OSIS book name, e.g. `Gen`
+    pub osis: &'static str,
+    pub testament: Testament,
+    /// English name, Italian name, then abbreviations and other aliases
+    pub names: &'static [&'static str],
+}
+
+impl Book {
+    /// Finds a book by any of its names or abbreviations, ignoring case, accents,
+    /// dots and spaces: `1 Cor`, `1cor.` and `I Corinzi` are all 1 Corinthians
+    pub fn find(name: &str) -> Option<&'static Book> {
+        let name = normalize(name);
+        BOOKS
+            .iter()
+            .find(|book| book.names.iter().any(|alias| normalize(alias) == name))
+    }
+
+    /// Finds a book by its OSIS name, e.g. `1Cor`
+    pub fn from_osis(osis: &str) -> Option<&'static Book> {
+        BOOKS.iter().find(|book| book.osis == osis)
+    }
+
+    /// Finds a book by the number used in the Zefania and Beblia XML bibles: 1 to 66
+    /// for the protestant canon, from 67 for the deuterocanonical books
+    pub fn from_number(number: usize) -> Option<&'static Book> {
+        let deuterocanon = ["JDT", "WIS", "TOB", "SIR", "BAR", "1MA", "2MA"];
+        let id = match number {
+            1..=66 => BOOKS
+                .iter()
+                .filter(|book| !deuterocanon.contains(&book.id))
+                .nth(number - 1)?
+                .id,
+            _ => deuterocanon.get(number.checked_sub(67)?)?,
+        };
+        BOOKS.iter().find(|book| book.id == id)
+    }
+
+    /// The full name of the book in English
+    pub fn english_name(&self) -> &'static str {
+        self.names[0]
+    }
+
+    /// The full name of the book in Italian
+    pub fn italian_name(&self) -> &'static str {
+        self.names[1]
+    }
+}
+
+/// A key to compare book names: the USFM code for the known books,
+/// the normalized name for the others
+pub fn book_key(name: &str) -> String {
+    match Book::find(name) {
+        Some(book) => book.id.to_string(),
+        None => normalize(name),
+    }
+}
+
+// Lowercase, without accents, dots and spaces, with roman numbers replaced by digits
+fn normalize(name: &str) -> String {
+    let name = fold_accents(&name.trim().to_lowercase()).replace('.', " ");
+    let name = name.trim_start();
+    let name = if let Some(rest) = name.strip_prefix("iii ") {
+        format!("3{}", rest)
+    } else if let Some(rest) = name.strip_prefix("ii ") {
+        format!("2{}", rest)
+    } else if let Some(rest) = name.strip_prefix("i ") {
+        format!("1{}", rest)
+    } else {
+        name.to_string()
+    };
+
+    name.split_whitespace().collect()
+}
+
+/// Replaces the accented latin letters with the plain ones, e.g. `Giosuè` with `Giosue`
+pub fn fold_accents(text: &str) -> String {
+    text.chars().map(fold_accent).collect()
+}
+
+/// Replaces an accented latin letter with the plain one, other characters are unchanged
+pub fn fold_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ä' | 'ã' | 'å' => 'a',
+        'À' | 'Á' | 'Â' | 'Ä' | 'Ã' | 'Å' => 'A',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'È' | 'É' | 'Ê' | 'Ë' => 'E',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'Ì' | 'Í' | 'Î' | 'Ï' => 'I',
+        'ò' | 'ó' | 'ô' | 'ö' | 'õ' => 'o',
+        'Ò' | 'Ó' | 'Ô' | 'Ö' | 'Õ' => 'O',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'Ù' | 'Ú' | 'Û' | 'Ü' => 'U',
+        'ç' => 'c',
+        'Ç' => 'C',
+        'ñ' => 'n',
+        'Ñ' => 'N',
+        _ => c,
+    }
+}
+
+macro_rules! book {
+    ($id:expr, $osis:expr, $testament:ident, [$($name:expr),+ $(,)?]) => {
+        Book {
+            id: $id,
+            osis: $osis,
+            testament: Testament::$testament,
+            names: &[$($name),+],
+        }
+    };
+}
+
+/// The books of the bible in canonical order, including the deuterocanonical
+/// books found in the Italian catholic translations
+pub static BOOKS: &[Book] = &[
+    book!("GEN", "Gen", Old, ["Genesis", "Genesi", "Gen", "Gn", "Ge"]),
+    book!("EXO", "Exod", Old, ["Exodus", "Esodo", "Exod", "Exo", "Ex", "Es"]),
+    book!("LEV", "Lev", Old, ["Leviticus", "Levitico", "Lev", "Lv", "Le"]),
+    book!("NUM", "Num", Old, ["Numbers", "Numeri", "Num", "Nm", "Nu", "Nb"]),
+    book!("DEU", "Deut", Old, ["Deuteronomy", "Deuteronomio", "Deut", "Deu", "Dt"]),
+    book!("JOS", "Josh", Old, ["Joshua", "Giosuè", "Josh", "Jos", "Gs", "Gios"]),
+    book!("JDG", "Judg", Old, ["Judges", "Giudici", "Judg", "Jdg", "Jg", "Gdc", "Giud"]),
+    book!("RUT", "Ruth", Old, ["Ruth", "Rut", "Rt", "Ru"]),
+    book!("1SA", "1Sam", Old, ["1 Samuel", "1 Samuele", "1 Sam", "1 Sa", "1 Sm"]),
+    book!("2SA", "2Sam", Old, ["2 Samuel", "2 Samuele", "2 Sam", "2 Sa", "2 Sm"]),
+    book!("1KI", "1Kgs", Old, ["1 Kings", "1 Re", "1 Kgs", "1 Ki", "1 Kin", "1 Rg"]),
+    book!("2KI", "2Kgs", Old, ["2 Kings", "2 Re", "2 Kgs", "2 Ki", "2 Kin", "2 Rg"]),
+    book!("1CH", "1Chr", Old, ["1 Chronicles", "1 Cronache", "1 Chr", "1 Ch", "1 Cr", "1 Cron"]),
+    book!("2CH", "2Chr", Old, ["2 Chronicles", "2 Cronache", "2 Chr", "2 Ch", "2 Cr", "2 Cron"]),
+    book!("EZR", "Ezra", Old, ["Ezra", "Esdra", "Ezr", "Esd"]),
+    book!("NEH", "Neh", Old, ["Nehemiah", "Neemia", "Neh", "Ne"]),
+    book!("TOB", "Tob", Old, ["Tobit", "Tobia", "Tob", "Tb"]),
+    book!("JDT", "Jdt", Old, ["Judith", "Giuditta", "Jdt", "Gdt"]),
+    book!("EST", "Esth", Old, ["Esther", "Ester", "Esth", "Est"]),
+    book!("1MA", "1Macc", Old, ["1 Maccabees", "1 Maccabei", "1 Macc", "1 Mac", "1 Mc"]),
+    book!("2MA", "2Macc", Old, ["2 Maccabees", "2 Maccabei", "2 Macc", "2 Mac", "2 Mc"]),
+    book!("JOB", "Job", Old, ["Job", "Giobbe", "Jb", "Gb"]),
+    book!("PSA", "Ps", Old, ["Psalms", "Salmi", "Psalm", "Ps", "Psa", "Salmo", "Sal", "Sl"]),
+    book!("PRO", "Prov", Old, ["Proverbs", "Proverbi", "Prov", "Pro", "Prv", "Pr"]),
+    book!("ECC", "Eccl", Old, ["Ecclesiastes", "Qoèlet", "Ecclesiaste", "Qoelet", "Eccl", "Ecc", "Qo"]),
+    book!("SNG", "Song", Old, ["Song of Songs", "Cantico dei Cantici", "Song of Solomon", "Cantico", "Song", "Cant", "Ct"]),
+    book!("WIS", "Wis", Old, ["Wisdom", "Sapienza", "Wis", "Sap"]),
+    book!("SIR", "Sir", Old, ["Sirach", "Siracide", "Ecclesiasticus", "Sir"]),
+    book!("ISA", "Isa", Old, ["Isaiah", "Isaia", "Isa", "Is"]),
+    book!("JER", "Jer", Old, ["Jeremiah", "Geremia", "Jer", "Ger"]),
+    book!("LAM", "Lam", Old, ["Lamentations", "Lamentazioni", "Lam", "La"]),
+    book!("BAR", "Bar", Old, ["Baruch", "Baruc", "Bar"]),
+    book!("EZK", "Ezek", Old, ["Ezekiel", "Ezechiele", "Ezek", "Eze", "Ez"]),
+    book!("DAN", "Dan", Old, ["Daniel", "Daniele", "Dan", "Dn", "Da"]),
+    book!("HOS", "Hos", Old, ["Hosea", "Osea", "Hos", "Os"]),
+    book!("JOL", "Joel", Old, ["Joel", "Gioele", "Jl", "Gl"]),
+    book!("AMO", "Amos", Old, ["Amos", "Amos", "Am"]),
+    book!("OBA", "Obad", Old, ["Obadiah", "Abdia", "Obad", "Ob", "Abd"]),
+    book!("JON", "Jonah", Old, ["Jonah", "Giona", "Jon", "Gio"]),
+    book!("MIC", "Mic", Old, ["Micah", "Michea", "Mic", "Mi"]),
+    book!("NAM", "Nah", Old, ["Nahum", "Naum", "Nah", "Na"]),
+    book!("HAB", "Hab", Old, ["Habakkuk", "Abacuc", "Hab", "Ab"]),
+    book!("ZEP", "Zeph", Old, ["Zephaniah", "Sofonia", "Zeph", "Zep", "Sof"]),
+    book!("HAG", "Hag", Old, ["Haggai", "Aggeo", "Hag", "Ag"]),
+    book!("ZEC", "Zech", Old, ["Zechariah", "Zaccaria", "Zech", "Zec", "Zc"]),
+    book!("MAL", "Mal", Old, ["Malachi", "Malachia", "Mal", "Ml"]),
+    book!("MAT", "Matt", New, ["Matthew", "Matteo", "Matt", "Mat", "Mt"]),
+    book!("MRK", "Mark", New, ["Mark", "Marco", "Mrk", "Mar", "Mk", "Mr", "Mc"]),
+    book!("LUK", "Luke", New, ["Luke", "Luca", "Luk", "Lk", "Lc", "Lu"]),
+    book!("JHN", "John", New, ["John", "Giovanni", "Jhn", "Jn", "Giov", "Gv"]),
+    book!("ACT", "Acts", New, ["Acts", "Atti degli Apostoli", "Atti", "Act", "Ac", "At"]),
+    book!("ROM", "Rom", New, ["Romans", "Romani", "Rom", "Ro", "Rm"]),
+    book!("1CO", "1Cor", New, ["1 Corinthians", "1 Corinzi", "1 Cor", "1 Co"]),
+    book!("2CO", "2Cor", New, ["2 Corinthians", "2 Corinzi", "2 Cor", "2 Co"]),
+    book!("GAL", "Gal", New, ["Galatians", "Galati", "Gal", "Ga"]),
+    book!("EPH", "Eph", New, ["Ephesians", "Efesini", "Eph", "Ef"]),
+    book!("PHP", "Phil", New, ["Philippians", "Filippesi", "Phil", "Php", "Fil"]),
+    book!("COL", "Col", New, ["Colossians", "Colossesi", "Col"]),
+    book!("1TH", "1Thess", New, ["1 Thessalonians", "1 Tessalonicesi", "1 Thess", "1 Tess", "1 Th", "1 Ts"]),
+    book!("2TH", "2Thess", New, ["2 Thessalonians", "2 Tessalonicesi", "2 Thess", "2 Tess", "2 Th", "2 Ts"]),
+    book!("1TI", "1Tim", New, ["1 Timothy", "1 Timoteo", "1 Tim", "1 Ti", "1 Tm"]),
+    book!("2TI", "2Tim", New, ["2 Timothy", "2 Timoteo", "2 Tim", "2 Ti", "2 Tm"]),
+    book!("TIT", "Titus", New, ["Titus", "Tito", "Tit", "Tt"]),
+    book!("PHM", "Phlm", New, ["Philemon", "Filemone", "Phlm", "Phm", "Flm", "Fm"]),
+    book!("HEB", "Heb", New, ["Hebrews", "Ebrei", "Heb", "Eb"]),
+    book!("JAS", "Jas", New, ["James", "Giacomo", "Jas", "Jm", "Giac", "Gc"]),
+    book!("1PE", "1Pet", New, ["1 Peter", "1 Pietro", "1 Pet", "1 Pe", "1 Pt", "1 Pi"]),
+    book!("2PE", "2Pet", New, ["2 Peter", "2 Pietro", "2 Pet", "2 Pe", "2 Pt", "2 Pi"]),
+    book!("1JN", "1John", New, ["1 John", "1 Giovanni", "1 Jn", "1 Jo", "1 Gv"]),
+    book!("2JN", "2John", New, ["2 John", "2 Giovanni", "2 Jn", "2 Jo", "2 Gv"]),
+    book!("3JN", "3John", New, ["3 John", "3 Giovanni", "3 Jn", "3 Jo", "3 Gv"]),
+    book!("JUD", "Jude", New, ["Jude", "Giuda", "Jud", "Jd", "Gd"]),
+    book!("REV", "Rev", New, ["Revelation", "Apocalisse", "Rev", "Apoc", "Ap", "Rv", "Re"]),
+];
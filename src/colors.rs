@@ -0,0 +1,108 @@
+use std::ffi::CString;
+use x11::{
+    xft::{XftColor, XftColorAllocName, XftColorAllocValue, XftColorFree},
+    xlib::{Colormap, Display, Visual},
+    xrender::XRenderColor,
+};
+
+/// Used when a color is not given or cannot be allocated
+pub const DEFAULT_FOREGROUND: &str = "white";
+pub const DEFAULT_BACKGROUND: &str = "black";
+
+/// The colors of the verses, allocated for the visual and the colormap of the window
+pub struct Colors {
+    pub foreground: XftColor,
+    pub reference: XftColor,
+    pub background: XftColor,
+}
+
+impl Colors {
+    /// Allocates the colors by name: an X color name, e.g. `gold`, `#rrggbb` or
+    /// `rgba:rr/gg/bb/aa`. An invalid color is reported and replaced by the default
+    pub fn alloc(
+        display: *mut Display,
+        visual: *mut Visual,
+        colormap: Colormap,
+        foreground: &str,
+        reference: &str,
+        background: &str,
+    ) -> Self {
+        let alloc = |name: &str, default: &str| {
+            alloc_color(display, visual, colormap, name).unwrap_or_else(|| {
+                eprintln!("invalid color '{}', using {}", name, default);
+                alloc_color(display, visual, colormap, default)
+                    .expect("the default colors are always available")
+            })
+        };
+
+        Self {
+            foreground: alloc(foreground, DEFAULT_FOREGROUND),
+            reference: alloc(reference, DEFAULT_FOREGROUND),
+            background: alloc(background, DEFAULT_BACKGROUND),
+        }
+    }
+
+    /// Gives the colors back to the colormap
+    pub fn free(&mut self, display: *mut Display, visual: *mut Visual, colormap: Colormap) {
+        for color in [&mut self.foreground, &mut self.reference, &mut self.background] {
+            unsafe { XftColorFree(display, visual, colormap, color) };
+        }
+    }
+}
+
+fn alloc_color(
+    display: *mut Display,
+    visual: *mut Visual,
+    colormap: Colormap,
+    name: &str,
+) -> Option<XftColor> {
+    let mut color = XftColor {
+        pixel: 0,
+        color: XRenderColor {
+            red: 0,
+            green: 0,
+            blue: 0,
+            alpha: 0,
+        },
+    };
+
+    // Xlib knows the names and the rgb: values, but not the alpha of the rgba: ones
+    let allocated = match parse_rgba(name) {
+        Some(value) => unsafe {
+            XftColorAllocValue(display, visual, colormap, &value, &mut color)
+        },
+        None => {
+            let name = CString::new(name).ok()?;
+            unsafe { XftColorAllocName(display, visual, colormap, name.as_ptr(), &mut color) }
+        }
+    };
+
+    (allocated != 0).then_some(color)
+}
+
+// Parses `rgba:r/g/b/a`, each component has 1 to 4 hexadecimal digits
+fn parse_rgba(value: &str) -> Option<XRenderColor> {
+    let components: Vec<u16> = value
+        .strip_prefix("rgba:")?
+        .split('/')
+        .map(|component| {
+            let digits = component.len() as u32;
+            if !(1..=4).contains(&digits) {
+                return None;
+            }
+            let value = u32::from_str_radix(component, 16).ok()?;
+            // Scaled to 16 bits, e.g. ff is 0xffff
+            Some((value * 0xffff / (16_u32.pow(digits) - 1)) as u16)
+        })
+        .collect::<Option<_>>()?;
+
+    match components[..] {
+        [red, green, blue, alpha] => Some(XRenderColor {
+            red,
+            green,
+            blue,
+            alpha,
+        }),
+        _ => None,
+    }
+}
@@ -0,0 +1,250 @@
+use crate::json;
+use crate::usfm::UsfmOptions;
+use crate::verse::{Verse, BIBLE_SEPARATOR};
+use crate::versesource::{self, Format, TextSource, VerseSource};
+use rusqlite::Connection;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// The formats a bible can be converted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// `book|chapter|verse|text` lines, as read by the screensaver
+    Pipe,
+    /// An array of `{"book", "chapter", "verse", "text"}` objects
+    Json,
+    /// `book,chapter,verse,text` rows with a header
+    Csv,
+    /// A `verses(book, chapter, verse, text)` table
+    Sqlite,
+}
+
+/// `convert [--from <format>] [--to <format>] <input> <output>`: converts a bible
+/// between formats and returns the exit code. The input format is detected when
+/// not given, the output is a `book|chapter|verse|text` file by default.
+pub fn run(program_name: &str, args: &[String]) -> i32 {
+    let usage = || {
+        eprintln!(
+            "usage: {} convert [--from pipe|osis|usfm|zefania|beblia|sqlite] \
+             [--to pipe|json|csv|sqlite] <input> <output>",
+            program_name
+        );
+        2
+    };
+
+    let mut from = None;
+    let mut to = Target::Pipe;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--from" => args.next().map(|name| name.parse().map(|format| from = Some(format))),
+            "--to" => args.next().map(|name| name.parse().map(|target| to = target)),
+            _ => {
+                paths.push(arg.as_str());
+                Some(Ok(()))
+            }
+        };
+        match parsed {
+            Some(Ok(())) => {}
+            Some(Err(e)) => {
+                eprintln!("{}: {}", program_name, e);
+                return 2;
+            }
+            None => return usage(),
+        }
+    }
+    let [input, output] = paths[..] else {
+        return usage();
+    };
+
+    match convert(input, from, output, to) {
+        Ok((verses, skipped)) => {
+            if skipped > 0 {
+                eprintln!("{}: {} malformed verses skipped", input, skipped);
+            }
+            eprintln!("{}: {} verses written", output, verses);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {}", program_name, e);
+            1
+        }
+    }
+}
+
+// Returns the number of verses written and of malformed verses skipped
+fn convert(
+    input: &str,
+    from: Option<Format>,
+    output: &str,
+    to: Target,
+) -> io::Result<(usize, usize)> {
+    let error = |path: &str, e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path, e));
+
+    let from = match from {
+        Some(format) => format,
+        None => Format::detect(input).map_err(|e| error(input, e))?,
+    };
+    // Text files are only scanned, no need to write their index
+    let source: Box<dyn VerseSource> = match from {
+        Format::Text => Box::new(TextSource::without_index(input)),
+        _ => versesource::open_as(input, from, &UsfmOptions::default())
+            .map_err(|e| error(input, e))?,
+    };
+
+    let mut verses = Vec::new();
+    let mut skipped = 0;
+    source
+        .scan(&mut |v| match v {
+            Ok(v) => verses.push(v),
+            Err(_) => skipped += 1,
+        })
+        .map_err(|e| error(input, e))?;
+    // Likely a wrong --from, better than writing an empty bible
+    if verses.is_empty() {
+        let e = io::Error::new(io::ErrorKind::InvalidData, "no verses found");
+        return Err(error(input, e));
+    }
+
+    // Write to a temporary file first so a failed conversion does not leave half a bible,
+    // but only over a regular file: a rename would replace a device or a symbolic link
+    // such as /dev/stdout
+    let special = match fs::symlink_metadata(output) {
+        Ok(metadata) => !metadata.is_file(),
+        Err(e) => e.kind() != io::ErrorKind::NotFound,
+    };
+    let tmp_path = if special {
+        PathBuf::from(output)
+    } else {
+        let mut tmp_path = PathBuf::from(output).into_os_string();
+        tmp_path.push(".tmp");
+        let _ = fs::remove_file(&tmp_path);
+        PathBuf::from(tmp_path)
+    };
+
+    let written = match to {
+        Target::Pipe => write_file(&tmp_path, |writer| write_pipe(writer, &verses)),
+        Target::Json => write_file(&tmp_path, |writer| write_json(writer, &verses)),
+        Target::Csv => write_file(&tmp_path, |writer| write_csv(writer, &verses)),
+        Target::Sqlite => write_sqlite(&tmp_path, &verses),
+    };
+    if special {
+        written.map_err(|e| error(output, e))?;
+    } else if let Err(e) = written.and_then(|_| fs::rename(&tmp_path, output)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(error(output, e));
+    }
+
+    Ok((verses.len(), skipped))
+}
+
+fn write_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer)?;
+    writer.flush()
+}
+
+fn write_pipe(writer: &mut impl Write, verses: &[Verse]) -> io::Result<()> {
+    for v in verses {
+        writeln!(
+            writer,
+            "{}{sep}{}{sep}{}{sep}{}",
+            v.book,
+            v.chapter,
+            v.verse,
+            v.text,
+            sep = BIBLE_SEPARATOR
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_json(writer: &mut impl Write, verses: &[Verse]) -> io::Result<()> {
+    writeln!(writer, "[")?;
+    for (i, v) in verses.iter().enumerate() {
+        let comma = if i + 1 < verses.len() { "," } else { "" };
+        writeln!(
+            writer,
+            "  {{\"book\": {}, \"chapter\": {}, \"verse\": {}, \"text\": {}}}{}",
+            json::string(&v.book),
+            v.chapter,
+            v.verse,
+            json::string(&v.text),
+            comma
+        )?;
+    }
+    writeln!(writer, "]")
+}
+
+fn write_csv(writer: &mut impl Write, verses: &[Verse]) -> io::Result<()> {
+    writeln!(writer, "book,chapter,verse,text")?;
+    for v in verses {
+        writeln!(
+            writer,
+            "{},{},{},{}",
+            csv_field(&v.book),
+            v.chapter,
+            v.verse,
+            csv_field(&v.text)
+        )?;
+    }
+
+    Ok(())
+}
+
+// A table which can be read back with -bible
+fn write_sqlite(path: &Path, verses: &[Verse]) -> io::Result<()> {
+    let mut connection = Connection::open(path).map_err(io::Error::other)?;
+    let transaction = connection.transaction().map_err(io::Error::other)?;
+    transaction
+        .execute_batch(
+            "CREATE TABLE verses(book TEXT NOT NULL, chapter INTEGER NOT NULL, \
+             verse INTEGER NOT NULL, text TEXT NOT NULL);",
+        )
+        .map_err(io::Error::other)?;
+    {
+        let mut statement = transaction
+            .prepare("INSERT INTO verses(book, chapter, verse, text) VALUES (?, ?, ?, ?)")
+            .map_err(io::Error::other)?;
+        for v in verses {
+            statement
+                .execute((&v.book, v.chapter, v.verse, &v.text))
+                .map_err(io::Error::other)?;
+        }
+    }
+    transaction.commit().map_err(io::Error::other)
+}
+
+// Quotes a CSV field when needed, doubling its quotes
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+impl std::str::FromStr for Target {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "pipe" | "text" | "txt" => Ok(Target::Pipe),
+            "json" => Ok(Target::Json),
+            "csv" => Ok(Target::Csv),
+            "sqlite" | "sqlite3" => Ok(Target::Sqlite),
+            _ => Err(format!(
+                "unknown output format '{}', use pipe, json, csv or sqlite",
+                name
+            )),
+        }
+    }
+}
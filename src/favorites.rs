@@ -0,0 +1,88 @@
+use crate::books::book_key;
+use crate::reference::Reference;
+use crate::verse::{Passage, BIBLE_SEPARATOR};
+use crate::versesource::VerseSource;
+use rand::{
+    distr::{weighted::WeightedIndex, Distribution},
+    Rng,
+};
+use std::{collections::HashMap, fs, io};
+
+// Comments start with this character
+const COMMENT: char = '#';
+
+/// A list of favorite passages, chosen with the probability given by their weight.
+/// The file has one reference per line, optionally followed by `|weight`, e.g. `Gv 3:16|5`.
+/// The passages are read from the bible being shown, so they are in its translation.
+#[derive(Debug, Clone)]
+pub struct Favorites {
+    passages: Vec<Passage>,
+    weights: WeightedIndex<f64>,
+    /// Probability of showing a favorite instead of a random verse of the bible
+    pub ratio: f64,
+}
+
+impl Favorites {
+    /// Reads the references of the file and their passages from the bible.
+    /// Returns the favorites with the references which are not in the bible
+    pub fn load(
+        path: &str,
+        ratio: f64,
+        source: &dyn VerseSource,
+    ) -> io::Result<(Self, Vec<Reference>)> {
+        let error = |line: usize, message: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+        };
+
+        let mut entries = Vec::new();
+        for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.split(COMMENT).next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (reference, weight) = match line.split_once(BIBLE_SEPARATOR) {
+                Some((reference, weight)) => (reference, weight.trim()),
+                None => (line, "1"),
+            };
+            let reference = Reference::parse(reference).map_err(|e| error(i + 1, e))?;
+            let weight = weight
+                .parse::<f64>()
+                .ok()
+                .filter(|weight| weight.is_finite() && *weight > 0.0)
+                .ok_or_else(|| error(i + 1, format!("invalid weight '{}'", weight)))?;
+            entries.push((reference, weight, Passage::default()));
+        }
+
+        // A single scan finds the verses of every reference
+        let mut keys: HashMap<String, String> = HashMap::new();
+        source.scan(&mut |v| {
+            let Ok(v) = v else {
+                return;
+            };
+            let key = keys.entry(v.book.clone()).or_insert_with(|| book_key(&v.book));
+            for (reference, _, passage) in entries.iter_mut() {
+                if reference.book_key() == key && reference.contains_position(v.chapter, v.verse) {
+                    passage.verses.push(v.clone());
+                }
+            }
+        })?;
+
+        let (found, missing): (Vec<_>, Vec<_>) =
+            entries.into_iter().partition(|(_, _, passage)| !passage.is_empty());
+        let weights = WeightedIndex::new(found.iter().map(|(_, weight, _)| *weight)).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "none of the favorites is in the bible")
+        })?;
+        let favorites = Self {
+            passages: found.into_iter().map(|(_, _, passage)| passage).collect(),
+            weights,
+            ratio,
+        };
+
+        Ok((favorites, missing.into_iter().map(|(reference, _, _)| reference).collect()))
+    }
+
+    /// A favorite passage, chosen by weight
+    pub fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> &Passage {
+        &self.passages[self.weights.sample(rng)]
+    }
+}
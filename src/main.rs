@@ -1,13 +1,49 @@
+mod bibleindex;
+mod books;
 mod bibleverse;
-use bibleverse::BibleVerse;
-use std::path::PathBuf;
+mod colors;
+use colors::{DEFAULT_BACKGROUND, DEFAULT_FOREGROUND};
+mod convert;
+mod favorites;
+use favorites::Favorites;
+mod json;
+mod monitors;
+mod osis;
+mod reference;
+use reference::Reference;
+mod search;
+mod show;
+mod sqlitebible;
+mod translation;
+use translation::Translation;
+mod usfm;
+use usfm::UsfmOptions;
+mod validate;
+mod verse;
+mod versefilter;
+use versefilter::VerseFilter;
+mod versehistory;
+use versehistory::VerseHistory;
+mod versematcher;
+use versematcher::VerseMatcher;
+mod versesource;
+mod xml;
+mod xmlbible;
+use bibleverse::{BibleVerse, Selection};
+use rand::{rngs::StdRng, SeedableRng};
 
 mod biblescreensaver;
-use biblescreensaver::ScreensaverSetup;
+use biblescreensaver::{Appearance, Layout, ScreensaverSetup, DEFAULT_FONT};
 
 const DEFAULT_DURATION: u64 = 30;
 const DEFAULT_LINE_LENGTH: i32 = 40;
 const DEFAULT_FONT_SIZE: i32 = 5;
+// About a day of verses with the default duration
+const DEFAULT_HISTORY_SIZE: usize = 3000;
+const DEFAULT_PASSAGE_LENGTH: usize = 1;
+const DEFAULT_WHOLE_CHAPTER_MAX: usize = 0;
+// Only the favorites are shown by default
+const DEFAULT_FAVORITES_RATIO: f64 = 1.0;
 
 use std::ffi::CString;
 use std::os::raw::c_char;
@@ -31,15 +67,70 @@ fn main() {
         .and_then(std::ffi::OsStr::to_str)
         .map(String::from)
         .unwrap();
+
+    // Subcommands, the screensaver is started otherwise
+    match args.get(1).map(String::as_str) {
+        Some("validate") => std::process::exit(validate::run(&program_name, &args[2..])),
+        Some("convert") => std::process::exit(convert::run(&program_name, &args[2..])),
+        Some("search") => std::process::exit(search::run(&program_name, &args[2..])),
+        Some("show") => std::process::exit(show::run(&program_name, &args[2..])),
+        _ => {}
+    }
+
     let option_index_duration = args.clone().into_iter().position(|x| x == "-duration");
     let option_index_line_length = args.clone().into_iter().position(|x| x == "-line-length");
     let option_index_font_size = args.clone().into_iter().position(|x| x == "-font-size");
-    let option_index_bible = args.clone().into_iter().position(|x| x == "-bible");
+    // -bible can be repeated: the other files are parallel translations of the first one
+    let option_indexes_bible: Vec<usize> = args
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| *x == "-bible")
+        .map(|(index, _)| index)
+        .collect();
+    // -parallel-bible adds a translation even when the first -bible is not given
+    let option_indexes_parallel_bible: Vec<usize> = args
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| *x == "-parallel-bible")
+        .map(|(index, _)| index)
+        .collect();
+    let option_index_parallel_layout =
+        args.clone().into_iter().position(|x| x == "-parallel-layout");
+    let option_index_seed = args.clone().into_iter().position(|x| x == "-seed");
+    let option_index_history = args.clone().into_iter().position(|x| x == "-history");
+    let option_index_salt = args.clone().into_iter().position(|x| x == "-salt");
+    let option_verse_of_the_day = args.contains(&String::from("-verse-of-the-day"));
+    let option_index_books = args.clone().into_iter().position(|x| x == "-books");
+    let option_index_testament = args.clone().into_iter().position(|x| x == "-testament");
+    let option_index_range = args.clone().into_iter().position(|x| x == "-range");
+    let option_index_passage_length =
+        args.clone().into_iter().position(|x| x == "-passage-length");
+    let option_index_whole_chapter_max =
+        args.clone().into_iter().position(|x| x == "-whole-chapter-max");
+    let option_index_ref = args.clone().into_iter().position(|x| x == "-ref");
+    let option_index_favorites = args.clone().into_iter().position(|x| x == "-favorites");
+    let option_index_favorites_ratio =
+        args.clone().into_iter().position(|x| x == "-favorites-ratio");
+    let option_index_topic = args.clone().into_iter().position(|x| x == "-topic");
+    let option_index_match = args.clone().into_iter().position(|x| x == "-match");
+    let option_index_fg = args.clone().into_iter().position(|x| x == "-fg");
+    let option_index_bg = args.clone().into_iter().position(|x| x == "-bg");
+    let option_index_ref_color = args.clone().into_iter().position(|x| x == "-ref-color");
+    let option_index_font = args.clone().into_iter().position(|x| x == "-font");
+    let option_index_ref_font = args.clone().into_iter().position(|x| x == "-ref-font");
+    let option_keep_notes = args.contains(&String::from("-keep-notes"));
+    let option_keep_styles = args.contains(&String::from("-keep-styles"));
 
     let duration: Option<u64>;
     let line_length: Option<i32>;
     let font_size: Option<i32>;
     let bible_path: Option<String>;
+    let layout: Layout;
+    let seed: Option<u64>;
+    let history_size: Option<usize>;
+    let verse_of_the_day: Option<String>;
+    let passage_length: Option<usize>;
+    let whole_chapter_max: Option<usize>;
 
     // Set default arguments if they are empty
     if let Some(index) = option_index_duration {
@@ -60,29 +151,280 @@ fn main() {
         font_size = Some(DEFAULT_FONT_SIZE);
     }
 
-    if let Some(index) = option_index_bible {
+    if let Some(index) = option_indexes_bible.first() {
         bible_path = Some(args[index + 1].parse().unwrap());
     } else {
-        let mut config_bible_path = PathBuf::new();
+        bible_path = Some(versesource::default_path(&program_name));
+    }
+
+    let translation_paths: Vec<String> = option_indexes_bible
+        .iter()
+        .skip(1)
+        .chain(&option_indexes_parallel_bible)
+        .map(|index| args[index + 1].clone())
+        .collect();
+
+    // Translations are stacked unless requested otherwise
+    if let Some(index) = option_index_parallel_layout {
+        layout = match args[index + 1].as_str() {
+            "stacked" => Layout::Stacked,
+            "side-by-side" => Layout::SideBySide,
+            other => {
+                eprintln!(
+                    "{}: invalid layout '{}', use stacked or side-by-side",
+                    program_name, other
+                );
+                std::process::exit(1);
+            }
+        };
+    } else {
+        layout = Layout::Stacked;
+    }
+
+    // Without a seed the verses and the animation are different at every run
+    if let Some(index) = option_index_seed {
+        seed = Some(args[index + 1].parse().unwrap());
+    } else {
+        seed = None;
+    }
+
+    // Number of recently shown verses which are not shown again, 0 disables the history
+    if let Some(index) = option_index_history {
+        history_size = Some(args[index + 1].parse().unwrap());
+    } else {
+        history_size = Some(DEFAULT_HISTORY_SIZE);
+    }
+
+    // The same salt gives the same verse of the day, different salts give different sequences
+    if option_verse_of_the_day {
+        if let Some(index) = option_index_salt {
+            verse_of_the_day = Some(args[index + 1].clone());
+        } else {
+            verse_of_the_day = Some(String::new());
+        }
+    } else {
+        verse_of_the_day = None;
+    }
+
+    // Show some consecutive verses together instead of a single one
+    if let Some(index) = option_index_passage_length {
+        passage_length = Some(args[index + 1].parse().unwrap());
+    } else {
+        passage_length = Some(DEFAULT_PASSAGE_LENGTH);
+    }
+
+    // Show short chapters in full, 0 disables it
+    if let Some(index) = option_index_whole_chapter_max {
+        whole_chapter_max = Some(args[index + 1].parse().unwrap());
+    } else {
+        whole_chapter_max = Some(DEFAULT_WHOLE_CHAPTER_MAX);
+    }
+
+    // Restrict the verses to some books, a testament or some ranges
+    let filter = match VerseFilter::new(
+        option_index_books.map(|index| args[index + 1].as_str()),
+        option_index_testament.map(|index| args[index + 1].as_str()),
+        option_index_range.map(|index| args[index + 1].as_str()),
+    ) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("{}: {}", program_name, e);
+            std::process::exit(1);
+        }
+    };
+
+    // Show only the verses about a topic or matching a regular expression
+    let matcher = match (option_index_topic, option_index_match) {
+        (Some(_), Some(_)) => Err(String::from("use either -topic or -match")),
+        (Some(index), None) => VerseMatcher::topic(&args[index + 1]).map(Some),
+        (None, Some(index)) => VerseMatcher::new(&args[index + 1], true).map(Some),
+        (None, None) => Ok(None),
+    };
+    let matcher = match matcher {
+        Ok(matcher) => matcher,
+        Err(e) => {
+            eprintln!("{}: {}", program_name, e);
+            std::process::exit(1);
+        }
+    };
+
+    // Always show the same passage
+    let reference = match option_index_ref.map(|index| Reference::parse(&args[index + 1])) {
+        Some(Ok(reference)) => Some(reference),
+        Some(Err(e)) => {
+            eprintln!("{}: {}", program_name, e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+
+    // Probability of showing a favorite instead of a random verse
+    let favorites_ratio = match option_index_favorites_ratio {
+        Some(index) => match args[index + 1].parse::<f64>() {
+            Ok(ratio) if (0.0..=1.0).contains(&ratio) => ratio,
+            _ => {
+                eprintln!(
+                    "{}: invalid favorites ratio '{}', use a number from 0 to 1",
+                    program_name,
+                    args[index + 1]
+                );
+                std::process::exit(1);
+            }
+        },
+        None => DEFAULT_FAVORITES_RATIO,
+    };
 
-        config_bible_path.push("/opt");
-        config_bible_path.push(&program_name);
-        config_bible_path.push("bible.txt");
-        bible_path = Some(String::from(config_bible_path.as_path().to_str().unwrap()));
+    let mut selection = Selection {
+        reference,
+        favorites: None,
+        verse_of_the_day,
+        filter,
+        matcher,
+        passage_length: passage_length.unwrap(),
+        whole_chapter_max: whole_chapter_max.unwrap(),
+    };
+
+    // Footnotes and character styles of the USFM files are left out unless requested
+    let usfm = UsfmOptions {
+        keep_notes: option_keep_notes,
+        keep_styles: option_keep_styles,
+    };
+
+    // The bible can be a text or an XML file, or a directory of USFM files
+    let bible_path = bible_path.unwrap();
+    let mut source = match versesource::open(&bible_path, &usfm) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}: {}: {}", program_name, bible_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(reference) = &selection.reference {
+        if let Err(e) = BibleVerse::referenced(source.as_ref(), reference) {
+            eprintln!("{}: {}: {}", program_name, reference, e);
+            std::process::exit(1);
+        }
     }
 
+    // The favorites are read from the bible, so that they are in its translation
+    if let Some(index) = option_index_favorites {
+        let favorites_path = &args[index + 1];
+        match Favorites::load(favorites_path, favorites_ratio, source.as_ref()) {
+            Ok((favorites, missing)) => {
+                for reference in missing {
+                    eprintln!("{}: {}: {} not found", program_name, favorites_path, reference);
+                }
+                selection.favorites = Some(favorites);
+            }
+            Err(e) => {
+                eprintln!("{}: {}: {}", program_name, favorites_path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // -ref and the favorites are looked up in the whole bible, whatever the source: only the
+    // random verses are restricted, and there are none with -ref
+    if selection.reference.is_none() {
+        if let Err(e) = source.restrict(&selection.filter) {
+            eprintln!("{}: {}: {}", program_name, bible_path, e);
+            std::process::exit(1);
+        }
+    }
+
+    // Better to stop now than to show nothing, -ref is shown anyway
+    if selection.reference.is_none() && !selection.accepts_all() {
+        let mut matched = false;
+        let scanned = source.scan(&mut |v| {
+            matched = matched || v.is_ok_and(|v| selection.accepts(&v));
+        });
+        if scanned.is_ok() && !matched {
+            // e.g. a misspelled book: -books 'Gensis'
+            let restrictions: Vec<String> = [
+                ("-books", option_index_books),
+                ("-testament", option_index_testament),
+                ("-range", option_index_range),
+                ("-topic", option_index_topic),
+                ("-match", option_index_match),
+            ]
+            .into_iter()
+            .filter_map(|(option, index)| {
+                index.map(|index| format!("{} '{}'", option, args[index + 1]))
+            })
+            .collect();
+            eprintln!(
+                "{}: no verse of {} matches {}",
+                program_name,
+                bible_path,
+                restrictions.join(" ")
+            );
+            std::process::exit(1);
+        }
+    }
+    let translations = Translation::load_all(&program_name, &translation_paths, &usfm);
+
+    let string_option = |index: Option<usize>, default: &str| {
+        index.map_or_else(|| default.to_string(), |index| args[index + 1].clone())
+    };
+
+    // The reference has the color of the text unless it is given
+    let foreground = string_option(option_index_fg, DEFAULT_FOREGROUND);
+    let reference_color = string_option(option_index_ref_color, &foreground);
+    let background = string_option(option_index_bg, DEFAULT_BACKGROUND);
+
+    // A reference font with only a style, e.g. `:bold`, is the font of the text in that style
+    let font = string_option(option_index_font, DEFAULT_FONT);
+    let reference_font = match option_index_ref_font.map(|index| args[index + 1].as_str()) {
+        Some(style) if style.starts_with(':') => format!("{}{}", font, style),
+        Some(reference_font) => reference_font.to_string(),
+        None => font.clone(),
+    };
+
     // Ok, start
-    if let Ok(mut s) = ScreensaverSetup::new(
-        line_length.unwrap(),
-        font_size.unwrap(),
-        bible_path.unwrap(),
+    let appearance = Appearance {
+        line_length: line_length.unwrap(),
+        font_size: font_size.unwrap(),
+        layout,
+        foreground,
+        reference_color,
+        background,
+        font,
+        reference_font,
+    };
+    match ScreensaverSetup::new(
+        appearance,
+        source,
         duration.unwrap(),
+        seed,
+        history_size.unwrap(),
+        selection.clone(),
+        translations,
     ) {
-        loop {
-            s.draw_e4verse();
+        // Until the window is closed
+        Ok(mut s) => while s.draw_e4verse() {},
+        Err((source, translations)) => {
+            // No X server, print the verse instead
+            let mut rng = match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_os_rng(),
+            };
+            let mut history = match seed {
+                Some(_) => VerseHistory::in_memory(history_size.unwrap()),
+                None => VerseHistory::load(history_size.unwrap()),
+            };
+            match BibleVerse::select(&mut rng, source.as_ref(), &mut history, &selection) {
+                Ok(e4verse) => {
+                    // Translations are printed one after the other
+                    for passage in e4verse.with_translations(&translations).passages() {
+                        println!("{}", verse::wrap(&passage.to_string(), line_length.unwrap()));
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}: {}", program_name, e);
+                    std::process::exit(1);
+                }
+            }
         }
-    } else {
-        let e4verse = BibleVerse::new(line_length.unwrap(), String::from(""));
-        println!("{}", e4verse);
     }
 }
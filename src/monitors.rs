@@ -0,0 +1,132 @@
+use std::ffi::{c_void, CString};
+use std::os::raw::c_int;
+use x11::{
+    xinerama::XineramaScreenInfo,
+    xlib::{Bool, Display, Status, Window, XDefaultRootWindow, XFree, XTranslateCoordinates},
+    xrandr::XRRMonitorInfo,
+};
+
+/// A rectangle of a window: x, y, width and height
+pub type Area = (i32, i32, u32, u32);
+
+// The functions of the libraries, loaded at run time: they are needed neither to build
+// nor to run the screensaver, which uses the whole window as a single monitor without them
+type XRRQueryExtension = unsafe extern "C" fn(*mut Display, *mut c_int, *mut c_int) -> Bool;
+type XRRQueryVersion = unsafe extern "C" fn(*mut Display, *mut c_int, *mut c_int) -> Status;
+type XRRGetMonitors =
+    unsafe extern "C" fn(*mut Display, Window, Bool, *mut c_int) -> *mut XRRMonitorInfo;
+type XRRFreeMonitors = unsafe extern "C" fn(*mut XRRMonitorInfo);
+type XineramaIsActive = unsafe extern "C" fn(*mut Display) -> Bool;
+type XineramaQueryScreens =
+    unsafe extern "C" fn(*mut Display, *mut c_int) -> *mut XineramaScreenInfo;
+
+/// The areas of a window shown by each monitor, as given by XRandR or else by Xinerama,
+/// or the whole window if neither is available or the window is on a single monitor
+pub fn query(display: *mut Display, window: Window, width: i32, height: i32) -> Vec<Area> {
+    let whole = (0, 0, width.max(1) as u32, height.max(1) as u32);
+
+    let root = unsafe { XDefaultRootWindow(display) };
+    let screens = unsafe { randr_monitors(display, root).or_else(|| xinerama_screens(display)) };
+    let Some(screens) = screens else {
+        return vec![whole];
+    };
+
+    // The monitors are placed on the root window, the window may be anywhere on it
+    let (mut x, mut y, mut child) = (0, 0, 0);
+    unsafe { XTranslateCoordinates(display, window, root, 0, 0, &mut x, &mut y, &mut child) };
+    let mut monitors: Vec<Area> = screens
+        .into_iter()
+        .filter_map(|(left, top, w, h)| intersection((left - x, top - y, w, h), whole))
+        .collect();
+    // Mirrored outputs show the same area
+    monitors.sort();
+    monitors.dedup();
+
+    if monitors.len() > 1 {
+        monitors
+    } else {
+        vec![whole]
+    }
+}
+
+// The monitors of XRandR 1.5
+unsafe fn randr_monitors(display: *mut Display, root: Window) -> Option<Vec<Area>> {
+    let library = library("libXrandr.so.2")?;
+    let query_extension: XRRQueryExtension = symbol(library, "XRRQueryExtension")?;
+    let query_version: XRRQueryVersion = symbol(library, "XRRQueryVersion")?;
+    let get_monitors: XRRGetMonitors = symbol(library, "XRRGetMonitors")?;
+    let free_monitors: XRRFreeMonitors = symbol(library, "XRRFreeMonitors")?;
+
+    // Older servers would answer the request with an error, which ends the program
+    let (mut event_base, mut error_base) = (0, 0);
+    let (mut major, mut minor) = (0, 0);
+    if query_extension(display, &mut event_base, &mut error_base) == 0
+        || query_version(display, &mut major, &mut minor) == 0
+        || (major, minor) < (1, 5)
+    {
+        return None;
+    }
+
+    let mut count = 0;
+    let monitors = get_monitors(display, root, 1, &mut count);
+    if monitors.is_null() {
+        return None;
+    }
+    let areas = std::slice::from_raw_parts(monitors, count.max(0) as usize)
+        .iter()
+        .map(|m| (m.x, m.y, m.width.max(0) as u32, m.height.max(0) as u32))
+        .collect();
+    free_monitors(monitors);
+
+    Some(areas)
+}
+
+// The screens of Xinerama
+unsafe fn xinerama_screens(display: *mut Display) -> Option<Vec<Area>> {
+    let library = library("libXinerama.so.1")?;
+    let is_active: XineramaIsActive = symbol(library, "XineramaIsActive")?;
+    let query_screens: XineramaQueryScreens = symbol(library, "XineramaQueryScreens")?;
+
+    if is_active(display) == 0 {
+        return None;
+    }
+
+    let mut count = 0;
+    let screens = query_screens(display, &mut count);
+    if screens.is_null() {
+        return None;
+    }
+    let areas = std::slice::from_raw_parts(screens, count.max(0) as usize)
+        .iter()
+        .map(|s| {
+            let (x, y) = (s.x_org as i32, s.y_org as i32);
+            (x, y, s.width.max(0) as u32, s.height.max(0) as u32)
+        })
+        .collect();
+    XFree(screens as *mut c_void);
+
+    Some(areas)
+}
+
+// Loads a shared library, which stays loaded
+unsafe fn library(name: &str) -> Option<*mut c_void> {
+    let name = CString::new(name).ok()?;
+    let library = libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+    (!library.is_null()).then_some(library)
+}
+
+// A function of a library, F must be the type of its pointer
+unsafe fn symbol<F: Copy>(library: *mut c_void, name: &str) -> Option<F> {
+    let name = CString::new(name).ok()?;
+    let symbol = libc::dlsym(library, name.as_ptr());
+    (!symbol.is_null()).then(|| std::mem::transmute_copy(&symbol))
+}
+
+// The common part of two areas, if any
+fn intersection(a: Area, b: Area) -> Option<Area> {
+    let x = a.0.max(b.0);
+    let y = a.1.max(b.1);
+    let right = (a.0 + a.2 as i32).min(b.0 + b.2 as i32);
+    let bottom = (a.1 + a.3 as i32).min(b.1 + b.3 as i32);
+    (right > x && bottom > y).then(|| (x, y, (right - x) as u32, (bottom - y) as u32))
+}
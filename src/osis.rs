@@ -0,0 +1,192 @@
+use crate::bibleverse::versereader;
+use crate::books::Book;
+use crate::verse::{finish, Verse};
+use crate::xml::attribute;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::{io, path::Path};
+
+/// Reads the verses of an OSIS XML bible, in document order.
+/// Both `<verse osisID="...">text</verse>` and the milestone form
+/// `<verse sID="..." osisID="..."/>text<verse eID="..."/>` are supported.
+/// Notes and titles are not part of the verse text.
+pub fn read(path: &Path) -> io::Result<Vec<Verse>> {
+    let mut reader = Reader::from_reader(versereader::BufReader::open(path)?);
+
+    let mut verses = Vec::new();
+    // Book names follow the language of the document, English by default
+    let mut italian = false;
+    // The verse being read, with its text so far
+    let mut current: Option<Verse> = None;
+    // Depth of the elements whose text is skipped
+    let mut skip: usize = 0;
+    let mut buffer = Vec::new();
+    loop {
+        let event = reader.read_event_into(&mut buffer).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("position {}: {}", reader.error_position(), e),
+            )
+        })?;
+        match event {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"osisText" => italian = language(&e).starts_with("it"),
+                b"verse" => {
+                    finish(&mut verses, current.take());
+                    current = start(&e, italian);
+                }
+                b"note" | b"title" => skip += 1,
+                _ => {}
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"verse" if attribute(&e, b"eID").is_some() => {
+                    finish(&mut verses, current.take());
+                }
+                b"verse" => {
+                    finish(&mut verses, current.take());
+                    current = start(&e, italian);
+                }
+                b"lb" => {
+                    if let Some(verse) = current.as_mut() {
+                        verse.text.push(' ');
+                    }
+                }
+                _ => {}
+            },
+            Event::End(e) => match e.local_name().as_ref() {
+                b"verse" => finish(&mut verses, current.take()),
+                b"note" | b"title" => skip = skip.saturating_sub(1),
+                _ => {}
+            },
+            Event::Text(e) if skip == 0 => {
+                if let Some(verse) = current.as_mut() {
+                    let unescaped = e
+                        .unescape()
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    verse.text.push_str(&unescaped);
+                }
+            }
+            Event::CData(e) if skip == 0 => {
+                if let Some(verse) = current.as_mut() {
+                    verse.text.push_str(&String::from_utf8_lossy(&e));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buffer.clear();
+    }
+    finish(&mut verses, current.take());
+
+    Ok(verses)
+}
+
+fn language(e: &BytesStart) -> String {
+    attribute(e, b"xml:lang").unwrap_or_default().to_lowercase()
+}
+
+// Starts a verse from its osisID, e.g. `John.3.16`. Only the first verse of a
+// range like `John.3.16 John.3.17` is used. Verses without a valid ID are skipped.
+fn start(e: &BytesStart, italian: bool) -> Option<Verse> {
+    let id = attribute(e, b"osisID")?;
+    let id = id.split_whitespace().next()?;
+    // Remove the work prefix, e.g. `Bible.KJV:John.3.16`
+    let id = id.rsplit(':').next()?;
+    // Remove the grain, e.g. `John.3.16!a`
+    let id = id.split('!').next()?;
+
+    let mut parts = id.split('.');
+    let osis = parts.next()?;
+    let chapter = parts.next()?.parse::<u32>().ok().filter(|c| *c > 0)?;
+    let verse = parts.next()?.parse::<u32>().ok().filter(|v| *v > 0)?;
+
+    let book = match Book::from_osis(osis) {
+        Some(book) if italian => book.italian_name(),
+        Some(book) => book.english_name(),
+        None => osis,
+    };
+
+    Some(Verse {
+        book: book.to_string(),
+        chapter,
+        verse,
+        text: String::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> Vec<Verse> {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "osis", name]
+            .iter()
+            .collect();
+        read(&path).unwrap()
+    }
+
+    fn verse(book: &str, chapter: u32, verse: u32, text: &str) -> Verse {
+        Verse {
+            book: book.to_string(),
+            chapter,
+            verse,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn container_verses() {
+        assert_eq!(
+            fixture("container.xml"),
+            vec![
+                verse(
+                    "John",
+                    3,
+                    16,
+                    "For God so loved the world, that he gave his only begotten Son",
+                ),
+                verse("John", 3, 17, "For God sent not his Son into the world"),
+            ]
+        );
+    }
+
+    #[test]
+    fn milestone_verses() {
+        assert_eq!(
+            fixture("milestones.xml"),
+            vec![
+                verse("Genesis", 1, 1, "In the beginning God created the heaven and the earth."),
+                verse("Genesis", 1, 2, "And the earth was without form, and void."),
+            ]
+        );
+    }
+
+    #[test]
+    fn notes_and_titles_are_skipped() {
+        let verses = fixture("container.xml");
+        assert!(verses.iter().all(|v| !v.text.contains("Nicodemus")));
+        assert!(verses.iter().all(|v| !v.text.contains("Or only begotten")));
+        assert!(fixture("milestones.xml").iter().all(|v| !v.text.contains("first day")));
+    }
+
+    #[test]
+    fn italian_book_names() {
+        assert_eq!(
+            fixture("italian.xml"),
+            vec![verse("Salmi", 23, 1, "Il Signore è il mio pastore")]
+        );
+    }
+
+    #[test]
+    fn osis_id_forms() {
+        assert_eq!(
+            fixture("osisids.xml"),
+            vec![
+                verse("Romans", 8, 28, "And we know that all things work together"),
+                verse("Romans", 8, 29, "For whom he did foreknow"),
+                verse("Romans", 8, 30, "Moreover whom he did predestinate"),
+            ]
+        );
+    }
+}
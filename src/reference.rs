@@ -0,0 +1,105 @@
+use crate::books::book_key;
+use std::fmt;
+
+// Separates the parts of a reference, e.g. `Sal 23:1-4,6`
+const PARTS_SEPARATOR: char = ',';
+
+/// A (chapter, verse) position in a book
+pub type Position = (u32, u32);
+
+/// Some verses of a book, e.g. `Gv 3:16`, `John 3:16-18`, `1 Cor 13`, `Sal 23:1-4,6`
+/// or a whole book. The book can be written with any of its names or abbreviations.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    // Key of the book, see books::book_key
+    key: String,
+    /// The (chapter, verse) ranges, both included
+    pub ranges: Vec<(Position, Position)>,
+    // The reference as written
+    text: String,
+}
+
+impl Reference {
+    /// Parses `Book`, then a comma separated list of `C`, `C-C`, `C:V`, `C:V-V` or `C:V-C:V`.
+    /// After a verse the following numbers are verses of the same chapter, as in `Sal 23:1-4,6`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let error = || format!("invalid reference '{}'", text);
+        let text = text.trim();
+
+        // The book name may start with a number too, e.g. `1 Cor 13`
+        let Some((book_end, last_letter)) = text.char_indices().rfind(|(_, c)| c.is_alphabetic())
+        else {
+            return Err(error());
+        };
+        let (book, numbers) = text.split_at(book_end + last_letter.len_utf8());
+        let book = book.trim();
+        let numbers = numbers.trim_start_matches('.').trim();
+
+        let mut ranges = Vec::new();
+        if numbers.is_empty() {
+            ranges.push(((0, 0), (u32::MAX, u32::MAX)));
+        }
+
+        let parse_number = |number: &str| number.trim().parse::<u32>().map_err(|_| error());
+        // A chapter, or a chapter and a verse
+        let parse_position = |position: &str| -> Result<(u32, Option<u32>), String> {
+            match position.split_once(':') {
+                Some((chapter, verse)) => Ok((parse_number(chapter)?, Some(parse_number(verse)?))),
+                None => Ok((parse_number(position)?, None)),
+            }
+        };
+        // The chapter of the last verse, for the verses which follow it
+        let mut chapter = None;
+        for part in numbers.split(PARTS_SEPARATOR).filter(|_| !numbers.is_empty()) {
+            let (first, last) = match part.split_once(['-', '–']) {
+                Some((first, last)) => (first, Some(last)),
+                None => (part, None),
+            };
+
+            let start = match (parse_position(first)?, chapter) {
+                ((c, Some(verse)), _) => (c, verse),
+                ((verse, None), Some(c)) => (c, verse),
+                // A whole chapter
+                ((c, None), None) => (c, 0),
+            };
+            let end = match last.map(parse_position).transpose()? {
+                Some((c, Some(verse))) => (c, verse),
+                Some((n, None)) if start.1 > 0 => (start.0, n),
+                Some((c, None)) => (c, u32::MAX),
+                None if start.1 > 0 => start,
+                None => (start.0, u32::MAX),
+            };
+            if start > end {
+                return Err(error());
+            }
+
+            chapter = (end.1 != u32::MAX).then_some(end.0);
+            ranges.push((start, end));
+        }
+
+        Ok(Self {
+            key: book_key(book),
+            ranges,
+            text: text.to_string(),
+        })
+    }
+
+    /// The key of the book, see books::book_key
+    pub fn book_key(&self) -> &str {
+        &self.key
+    }
+
+    /// True if the chapter and verse are referenced, the book is not checked
+    pub fn contains_position(&self, chapter: u32, verse: u32) -> bool {
+        let position = (chapter, verse);
+        self.ranges
+            .iter()
+            .any(|(start, end)| *start <= position && position <= *end)
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
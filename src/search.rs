@@ -0,0 +1,131 @@
+use crate::usfm::UsfmOptions;
+use crate::verse::Verse;
+use crate::versefilter::VerseFilter;
+use crate::versematcher::VerseMatcher;
+use crate::versesource;
+use std::io::{IsTerminal, Write};
+
+// Terminal escapes around the highlighted matches
+const HIGHLIGHT_START: &str = "\x1b[1;31m";
+const HIGHLIGHT_END: &str = "\x1b[0m";
+
+/// `search <text> [--bible <file>] [--book <books>] [--regex] [--limit <number>]`:
+/// prints the verses containing a text, ignoring case and accents, and returns the
+/// exit code, 1 if no verse is found and 2 if the bible can not be read
+pub fn run(program_name: &str, args: &[String]) -> i32 {
+    let usage = || {
+        eprintln!(
+            "usage: {} search <text> [--bible <file>] [--book <books>] [--regex] \
+             [--limit <number>]",
+            program_name
+        );
+        2
+    };
+
+    let mut bible_path = versesource::default_path(program_name);
+    let mut books: Vec<&str> = Vec::new();
+    let mut is_regex = false;
+    let mut limit = None;
+    let mut patterns = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bible" => match args.next() {
+                Some(path) => bible_path = path.clone(),
+                None => return usage(),
+            },
+            // Can be repeated, or list some books like -books
+            "--book" => match args.next() {
+                Some(book) => books.push(book),
+                None => return usage(),
+            },
+            "--regex" => is_regex = true,
+            "--limit" => match args.next().map(|limit| limit.parse::<usize>()) {
+                Some(Ok(number)) => limit = Some(number),
+                _ => return usage(),
+            },
+            _ => patterns.push(arg.as_str()),
+        }
+    }
+    let [pattern] = patterns[..] else {
+        return usage();
+    };
+
+    let matcher = match VerseMatcher::new(pattern, is_regex) {
+        Ok(matcher) => matcher,
+        Err(e) => {
+            eprintln!("{}: {}", program_name, e);
+            return 2;
+        }
+    };
+    let books = books.join(",");
+    let filter = match VerseFilter::new((!books.is_empty()).then_some(&books), None, None) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("{}: {}", program_name, e);
+            return 2;
+        }
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    let highlight = stdout.is_terminal();
+    // Stop printing when stdout is closed, e.g. piped to head
+    let mut closed = false;
+    let mut found = 0;
+    let source = versesource::open_for_scan(&bible_path, &UsfmOptions::default());
+    let scanned = source.and_then(|mut source| {
+        source.restrict(&filter)?;
+        // Malformed verses can not be found
+        source.scan(&mut |v| {
+            let Ok(v) = v else {
+                return;
+            };
+            if !filter.accepts(&v) {
+                return;
+            }
+            let matches = matcher.find_all(&v.text);
+            if matches.is_empty() {
+                return;
+            }
+            found += 1;
+            if !closed && limit.is_none_or(|limit| found <= limit) {
+                closed = writeln!(stdout, "{}", format_match(&v, &matches, highlight)).is_err();
+            }
+        })
+    });
+    if let Err(e) = scanned {
+        eprintln!("{}: {}: {}", program_name, bible_path, e);
+        return 2;
+    }
+
+    match limit {
+        Some(limit) if found > limit => eprintln!("{} verses found, {} shown", found, limit),
+        _ => eprintln!("{} verses found", found),
+    }
+
+    if found > 0 {
+        0
+    } else {
+        1
+    }
+}
+
+// The verse with its reference, and the matches highlighted on a terminal
+fn format_match(verse: &Verse, matches: &[std::ops::Range<usize>], highlight: bool) -> String {
+    if !highlight {
+        return verse.to_string();
+    }
+
+    let mut text = String::new();
+    let mut last = 0;
+    for found in matches {
+        text.push_str(&verse.text[last..found.start]);
+        text.push_str(HIGHLIGHT_START);
+        text.push_str(&verse.text[found.clone()]);
+        text.push_str(HIGHLIGHT_END);
+        last = found.end;
+    }
+    text.push_str(&verse.text[last..]);
+
+    format!("[{}] {}", verse.reference(), text)
+}
@@ -0,0 +1,59 @@
+use crate::bibleverse::BibleVerse;
+use crate::reference::Reference;
+use crate::usfm::UsfmOptions;
+use crate::versesource;
+
+/// `show <reference> [--bible <file>]`: prints the verses of a reference, e.g. `Sal 23:1-4,6`,
+/// one per line, and returns the exit code, 1 if no verse is found and 2 on errors
+pub fn run(program_name: &str, args: &[String]) -> i32 {
+    let usage = || {
+        eprintln!("usage: {} show <reference> [--bible <file>]", program_name);
+        2
+    };
+
+    let mut bible_path = versesource::default_path(program_name);
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bible" => match args.next() {
+                Some(path) => bible_path = path.clone(),
+                None => return usage(),
+            },
+            // The reference can be given without quotes, e.g. `show 1 Cor 13`
+            _ => words.push(arg.as_str()),
+        }
+    }
+    if words.is_empty() {
+        return usage();
+    }
+
+    let reference = match Reference::parse(&words.join(" ")) {
+        Ok(reference) => reference,
+        Err(e) => {
+            eprintln!("{}: {}", program_name, e);
+            return 2;
+        }
+    };
+
+    let source = match versesource::open_for_scan(&bible_path, &UsfmOptions::default()) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}: {}: {}", program_name, bible_path, e);
+            return 2;
+        }
+    };
+
+    match BibleVerse::referenced(source.as_ref(), &reference) {
+        Ok(passage) => {
+            for v in &passage.verses {
+                println!("{}", v);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {}: {}", program_name, reference, e);
+            1
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use crate::books::{Book, BOOKS};
+use crate::verse::{Verse, VerseError};
+use crate::versefilter::VerseFilter;
+use crate::versesource::VerseSource;
+use rusqlite::{params_from_iter, types::Value, Connection, OpenFlags, OptionalExtension};
+use std::{collections::HashMap, io};
+
+/// A bible kept in a SQLite database, e.g. a MySword or MyBible module or
+/// any database with a `verses(book, chapter, verse, text)` table.
+/// The filter is applied in SQL, and the verses are read one at a time by rowid.
+pub struct SqliteSource {
+    connection: Connection,
+    // Quoted names of the table and of its book, chapter, verse and text columns
+    table: String,
+    columns: [String; 4],
+    // Book names by the value of the book column
+    names: HashMap<String, String>,
+    // SQL condition of the filter, with its parameters
+    condition: String,
+    parameters: Vec<Value>,
+    // Rowids of the verses accepted by the filter, in canonical order
+    rowids: Vec<i64>,
+}
+
+// Names of the columns holding the parts of a verse, as found in the common modules
+const BOOK_COLUMNS: &[&str] = &["book", "book_number", "booknumber", "bookid"];
+const CHAPTER_COLUMNS: &[&str] = &["chapter", "chapter_number", "chapternumber"];
+const VERSE_COLUMNS: &[&str] = &["verse", "verse_number", "versenumber"];
+const TEXT_COLUMNS: &[&str] = &["text", "scripture", "content"];
+
+impl SqliteSource {
+    /// Opens a database read only and finds the table of the verses
+    pub fn open(path: &str) -> io::Result<Self> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let connection = Connection::open_with_flags(path, flags).map_err(io::Error::other)?;
+
+        let (table, columns) = find_table(&connection)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "no table of verses found")
+        })?;
+        let names = book_names(&connection, &table, &columns[0]).map_err(io::Error::other)?;
+
+        let mut source = Self {
+            connection,
+            columns: columns.map(|column| quote(&column)),
+            table: quote(&table),
+            names,
+            condition: String::from("1"),
+            parameters: Vec::new(),
+            rowids: Vec::new(),
+        };
+        source.load_rowids()?;
+
+        Ok(source)
+    }
+
+    // Reads the rowids of the verses matching the condition. The books are sorted in
+    // canonical order, the unknown ones in order of appearance, then chapters and verses
+    fn load_rowids(&mut self) -> io::Result<()> {
+        let sql = format!(
+            "SELECT rowid, {} FROM {} WHERE {} ORDER BY rowid",
+            self.columns[..3].join(", "),
+            self.table,
+            self.condition
+        );
+        let mut statement = self.connection.prepare(&sql).map_err(io::Error::other)?;
+        let mut rows: Vec<(i64, String, i64, i64)> = statement
+            .query_map(params_from_iter(&self.parameters), |row| {
+                let number = |i| -> rusqlite::Result<i64> {
+                    Ok(key(&row.get::<_, Value>(i)?).parse().unwrap_or(0))
+                };
+                Ok((row.get(0)?, key(&row.get::<_, Value>(1)?), number(2)?, number(3)?))
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(io::Error::other)?;
+        drop(statement);
+
+        let mut ranks: HashMap<String, usize> = HashMap::new();
+        for (_, book, _, _) in &rows {
+            if !ranks.contains_key(book) {
+                let name = self.names.get(book).unwrap_or(book);
+                let rank = match Book::find(name) {
+                    Some(found) => BOOKS.iter().position(|book| book.id == found.id).unwrap_or(0),
+                    None => BOOKS.len() + ranks.len(),
+                };
+                ranks.insert(book.clone(), rank);
+            }
+        }
+        rows.sort_by_key(|(rowid, book, chapter, verse)| (ranks[book], *chapter, *verse, *rowid));
+        self.rowids = rows.into_iter().map(|(rowid, _, _, _)| rowid).collect();
+
+        Ok(())
+    }
+
+    // Builds a verse from a row, validated as a line of the text format
+    fn verse(&self, row: &rusqlite::Row, position: usize) -> rusqlite::Result<Result<Verse, VerseError>> {
+        let book = key(&row.get::<_, Value>(0)?);
+        let book = self.names.get(&book).cloned().unwrap_or(book);
+        let chapter = key(&row.get::<_, Value>(1)?);
+        let verse = key(&row.get::<_, Value>(2)?);
+        let text = strip_markup(&key(&row.get::<_, Value>(3)?));
+        let line = format!("{}|{}|{}|{}", book, chapter, verse, text);
+
+        Ok(Verse::parse(&line, position + 1))
+    }
+}
+
+impl VerseSource for SqliteSource {
+    fn len(&self) -> Option<usize> {
+        Some(self.rowids.len())
+    }
+
+    fn get(&self, position: usize) -> Option<Result<Verse, VerseError>> {
+        let rowid = self.rowids.get(position)?;
+        let sql = format!("SELECT {} FROM {} WHERE rowid = ?", self.columns.join(", "), self.table);
+        let mut statement = self.connection.prepare_cached(&sql).ok()?;
+        statement
+            .query_row([rowid], |row| self.verse(row, position))
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    fn scan(&self, f: &mut dyn FnMut(Result<Verse, VerseError>)) -> io::Result<()> {
+        let sql = format!(
+            "SELECT {} FROM {} WHERE rowid = ?",
+            self.columns.join(", "),
+            self.table
+        );
+        let mut statement = self.connection.prepare(&sql).map_err(io::Error::other)?;
+        for (position, rowid) in self.rowids.iter().enumerate() {
+            let v = statement
+                .query_row([rowid], |row| self.verse(row, position))
+                .map_err(io::Error::other)?;
+            f(v);
+        }
+
+        Ok(())
+    }
+
+    fn restrict(&mut self, filter: &VerseFilter) -> io::Result<()> {
+        if filter.is_empty() {
+            return Ok(());
+        }
+
+        let [book, chapter, verse, _] = &self.columns;
+        let mut conditions = Vec::new();
+        let mut parameters = Vec::new();
+        let mut values: Vec<&String> = self.names.keys().collect();
+        values.sort();
+        for value in values {
+            let Some(ranges) = filter.book_ranges(&self.names[value]) else {
+                continue;
+            };
+            for (start, end) in ranges {
+                // Books are compared as text, whatever the type of their column
+                conditions.push(format!(
+                    "(CAST({} AS TEXT) = ? AND ({}, {}) BETWEEN (?, ?) AND (?, ?))",
+                    book, chapter, verse
+                ));
+                parameters.push(Value::Text(value.clone()));
+                parameters.extend([start.0, start.1, end.0, end.1].map(|n| Value::Integer(n as i64)));
+            }
+        }
+
+        self.condition = match conditions.is_empty() {
+            true => String::from("0"),
+            false => conditions.join(" OR "),
+        };
+        self.parameters = parameters;
+        self.load_rowids()
+    }
+}
+
+// Finds a table with the book, chapter, verse and text columns
+fn find_table(connection: &Connection) -> io::Result<Option<(String, [String; 4])>> {
+    let tables: Vec<String> = connection
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+        .and_then(|mut statement| {
+            statement
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })
+        .map_err(io::Error::other)?;
+
+    for table in tables {
+        let columns: Vec<String> = connection
+            .prepare(&format!("PRAGMA table_info({})", quote(&table)))
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| row.get(1))?
+                    .collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .map_err(io::Error::other)?;
+        let find = |names: &[&str]| {
+            columns
+                .iter()
+                .find(|column| names.contains(&column.to_lowercase().as_str()))
+                .cloned()
+        };
+        if let (Some(book), Some(chapter), Some(verse), Some(text)) = (
+            find(BOOK_COLUMNS),
+            find(CHAPTER_COLUMNS),
+            find(VERSE_COLUMNS),
+            find(TEXT_COLUMNS),
+        ) {
+            return Ok(Some((table, [book, chapter, verse, text])));
+        }
+    }
+
+    Ok(None)
+}
+
+// The name of each book: from the `books` table of the MyBible modules, from the book
+// number as in the MySword modules, or the value of the book column itself
+fn book_names(
+    connection: &Connection,
+    table: &str,
+    book: &str,
+) -> rusqlite::Result<HashMap<String, String>> {
+    let sql = format!("SELECT DISTINCT {} FROM {}", quote(book), quote(table));
+    let values: Vec<Value> = connection
+        .prepare(&sql)?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<Value>>>()?;
+
+    let named: HashMap<String, String> = connection
+        .prepare("SELECT book_number, long_name FROM books")
+        .and_then(|mut statement| {
+            statement
+                .query_map([], |row| Ok((key(&row.get(0)?), row.get::<_, String>(1)?)))?
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let italian = ["SELECT value FROM info WHERE name = 'language'", "SELECT Language FROM Details"]
+        .iter()
+        .filter_map(|sql| connection.query_row(sql, [], |row| row.get::<_, String>(0)).ok())
+        .any(|language| language.to_lowercase().starts_with("it"));
+
+    let names = values
+        .iter()
+        .map(|value| {
+            let key = key(value);
+            let name = match (named.get(&key), value) {
+                (Some(name), _) => name.trim().to_string(),
+                (None, Value::Integer(number)) => match Book::from_number(*number as usize) {
+                    Some(book) if italian => book.italian_name().to_string(),
+                    Some(book) => book.english_name().to_string(),
+                    None => key.clone(),
+                },
+                (None, _) => key.clone(),
+            };
+            (key, name)
+        })
+        .collect();
+
+    Ok(names)
+}
+
+// A value of a column as text
+fn key(value: &Value) -> String {
+    match value {
+        Value::Integer(number) => number.to_string(),
+        Value::Real(number) => number.to_string(),
+        Value::Text(text) => text.clone(),
+        Value::Blob(blob) => String::from_utf8_lossy(blob).into_owned(),
+        Value::Null => String::new(),
+    }
+}
+
+// Quotes a table or column name
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// Removes the tags of the MySword and MyBible modules, with the text of notes and
+// Strong numbers, e.g. `<RF>note<Rf>`, `<f>note</f>` and `<S>1234</S>`
+fn strip_markup(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut skip: usize = 0;
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        if skip == 0 {
+            stripped.push_str(&rest[..start]);
+        }
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        match (tag.starts_with('/'), name) {
+            (false, "RF" | "f" | "n" | "S") => skip += 1,
+            (false, "Rf") | (true, "f" | "n" | "S") => skip = skip.saturating_sub(1),
+            // Line and paragraph breaks separate words
+            (_, "br" | "CM" | "CL" | "pb") => stripped.push(' '),
+            _ => {}
+        }
+        rest = &rest[start + end + 1..];
+    }
+    if skip == 0 {
+        stripped.push_str(rest);
+    }
+
+    stripped.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
@@ -0,0 +1,69 @@
+use crate::books::book_key;
+use crate::usfm::UsfmOptions;
+use crate::verse::{Passage, Verse};
+use crate::versesource;
+use std::{collections::HashMap, io};
+
+/// Another translation of the bible, whose verses are shown next to the ones
+/// chosen from the main bible file. The verses are kept in memory, by reference.
+pub struct Translation {
+    // Verses by book key, chapter and verse number
+    verses: HashMap<(String, u32, u32), Verse>,
+}
+
+impl Translation {
+    /// Reads every verse of a bible file, skipping the malformed ones
+    pub fn load(path: &str, usfm: &UsfmOptions) -> io::Result<Self> {
+        // Only scanned, its index is not needed
+        let source = versesource::open_for_scan(path, usfm)?;
+
+        let mut verses = HashMap::new();
+        // Books are named the same way in the whole file, compute their keys once
+        let mut keys: HashMap<String, String> = HashMap::new();
+        source.scan(&mut |v| {
+            if let Ok(v) = v {
+                let key = keys
+                    .entry(v.book.clone())
+                    .or_insert_with(|| book_key(&v.book))
+                    .clone();
+                verses.insert((key, v.chapter, v.verse), v);
+            }
+        })?;
+
+        Ok(Self { verses })
+    }
+
+    /// Loads the translations which can be read, reporting the others on stderr
+    pub fn load_all(program_name: &str, paths: &[String], usfm: &UsfmOptions) -> Vec<Self> {
+        paths
+            .iter()
+            .filter_map(|path| match Self::load(path, usfm) {
+                Ok(translation) => Some(translation),
+                Err(e) => {
+                    eprintln!("{}: {}: {}", program_name, path, e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The verses of this translation with the same references of the passage,
+    /// matching the books by name, e.g. `Giovanni 3:16` with `John 3:16`.
+    /// Verses missing in this translation are skipped.
+    pub fn align(&self, passage: &Passage) -> Passage {
+        let mut keys: HashMap<&str, String> = HashMap::new();
+        let verses = passage
+            .verses
+            .iter()
+            .filter_map(|v| {
+                let key = keys
+                    .entry(v.book.as_str())
+                    .or_insert_with(|| book_key(&v.book))
+                    .clone();
+                self.verses.get(&(key, v.chapter, v.verse)).cloned()
+            })
+            .collect();
+
+        Passage { verses }
+    }
+}
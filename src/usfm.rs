@@ -0,0 +1,249 @@
+use crate::bibleverse::versereader;
+use crate::books::BOOKS;
+use crate::verse::{finish, Verse};
+use std::{fs, io, io::Read, path::Path};
+
+/// What is kept of the USFM markup besides the verse text
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UsfmOptions {
+    /// Keep footnotes and cross references, in parentheses after the word they refer to
+    pub keep_notes: bool,
+    /// Keep the character style markers, e.g. `\wj ...\wj*`, as they are
+    pub keep_styles: bool,
+}
+
+/// Reads the `.usfm` and `.sfm` files of a directory, one book per file, even if compressed,
+/// and returns their verses with the books in canonical order
+pub fn read_dir(path: &Path, options: &UsfmOptions) -> io::Result<Vec<Verse>> {
+    let mut books = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+        // Compressed files are accepted too, e.g. `44JHN.usfm.gz`
+        let name = ["gz", "zst", "xz"]
+            .iter()
+            .find_map(|extension| name.strip_suffix(&format!(".{}", extension)))
+            .unwrap_or(&name);
+        if path.is_file() && (name.ends_with(".usfm") || name.ends_with(".sfm")) {
+            let (id, verses) = parse(&read_to_string(&path)?, options);
+            // Unknown books go after the known ones, in file name order
+            let order = BOOKS.iter().position(|book| Some(book.id) == id.as_deref());
+            books.push((order.unwrap_or(BOOKS.len()), path, verses));
+        }
+    }
+    books.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+
+    Ok(books.into_iter().flat_map(|(_, _, verses)| verses).collect())
+}
+
+/// Reads a single `.usfm` or `.sfm` file
+pub fn read(path: &Path, options: &UsfmOptions) -> io::Result<Vec<Verse>> {
+    Ok(parse(&read_to_string(path)?, options).1)
+}
+
+// Reads a whole file, decompressing it if needed
+fn read_to_string(path: &Path) -> io::Result<String> {
+    let mut text = String::new();
+    versereader::BufReader::open(path)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+// Where the text following a marker goes
+enum Target {
+    // The text of the current verse
+    Verse,
+    // The text of a footnote or cross reference
+    Note,
+    // Nowhere, e.g. headings, introductions and note references
+    Skip,
+}
+
+// Returns the `\id` code of a book with its verses
+fn parse(text: &str, options: &UsfmOptions) -> (Option<String>, Vec<Verse>) {
+    let mut id: Option<String> = None;
+    let mut header: Option<String> = None;
+    // The short name of the `\toc2` marker, used when there is no header
+    let mut short_name: Option<String> = None;
+    let mut chapter = 0;
+    let mut verses = Vec::new();
+    let mut current: Option<Verse> = None;
+
+    // Text outside any verse, e.g. before the first `\v` of a chapter, is skipped
+    let mut target = Target::Skip;
+    let mut in_note = false;
+    // Inside a character style, the text after `|` is a list of attributes
+    let mut in_style = false;
+    let mut note = String::new();
+
+    // Every marker starts with a backslash: the text before the first one is not USFM
+    for segment in text.split('\\').skip(1) {
+        let end = segment
+            .find(|c: char| c.is_whitespace() || c == '*')
+            .map(|end| if segment[end..].starts_with('*') { end + 1 } else { end })
+            .unwrap_or(segment.len());
+        let (marker, rest) = segment.split_at(end);
+        // Nested character styles are marked with a `+`, e.g. `\+wj`
+        let marker = marker.trim_start_matches('+');
+        let closing = marker.ends_with('*');
+        // An opening marker is followed by a single space which is not part of the text
+        let rest = match closing {
+            true => rest,
+            false => rest.strip_prefix([' ', '\t', '\r', '\n']).unwrap_or(rest),
+        };
+        let name = marker.trim_end_matches('*');
+        // Numbered markers like `\q1` and `\s2` behave like `\q` and `\s`
+        let base = name.trim_end_matches(|c: char| c.is_ascii_digit());
+
+        let mut text = rest;
+        match base {
+            "id" => {
+                let mut words = rest.split_whitespace();
+                id = words.next().map(str::to_uppercase);
+                // A file may hold more books, each with its own names
+                header = None;
+                short_name = None;
+                target = Target::Skip;
+                continue;
+            }
+            "h" => {
+                header = Some(rest.trim().to_string()).filter(|h| !h.is_empty());
+                target = Target::Skip;
+                continue;
+            }
+            "toc" if name == "toc2" => {
+                short_name = Some(rest.trim().to_string()).filter(|n| !n.is_empty());
+                target = Target::Skip;
+                continue;
+            }
+            "c" => {
+                finish(&mut verses, current.take());
+                chapter = first_number(rest).unwrap_or(0);
+                target = Target::Skip;
+                continue;
+            }
+            "v" => {
+                finish(&mut verses, current.take());
+                let number = rest.split_whitespace().next().unwrap_or_default();
+                text = rest.trim_start()[number.len()..].trim_start();
+                target = Target::Verse;
+                current = match first_number(number) {
+                    Some(verse) if chapter > 0 => Some(Verse {
+                        book: book_name(id.as_deref(), header.as_ref().or(short_name.as_ref())),
+                        chapter,
+                        verse,
+                        text: String::new(),
+                    }),
+                    _ => None,
+                };
+            }
+            "f" | "fe" | "x" | "ef" | "ex" if !closing => {
+                in_note = true;
+                note.clear();
+                // The note caller, e.g. `+`, comes first
+                let caller = rest.split_whitespace().next().unwrap_or_default();
+                text = rest.trim_start()[caller.len()..].trim_start();
+                target = Target::Note;
+            }
+            "f" | "fe" | "x" | "ef" | "ex" => {
+                in_note = false;
+                if let Some(verse) = current.as_mut() {
+                    let note = note.split_whitespace().collect::<Vec<&str>>().join(" ");
+                    if options.keep_notes && !note.is_empty() {
+                        verse.text.push_str(&format!(" ({})", note));
+                    }
+                }
+                target = Target::Verse;
+            }
+            // The origin reference of a note repeats the verse number
+            "fr" | "xo" => target = Target::Skip,
+            _ if in_note => target = Target::Note,
+            // Alternate and published numbers, figures: not part of the text
+            "va" | "vp" | "ca" | "cp" | "fig" | "cat" | "rb" if !closing => target = Target::Skip,
+            "va" | "vp" | "ca" | "cp" | "fig" | "cat" | "rb" => target = Target::Verse,
+            _ if is_heading(base) => target = Target::Skip,
+            _ if is_character_style(base) => {
+                in_style = !closing;
+                if options.keep_styles {
+                    if let Some(verse) = current.as_mut() {
+                        verse.text.push('\\');
+                        verse.text.push_str(marker);
+                        if !closing {
+                            verse.text.push(' ');
+                        }
+                    }
+                }
+                // Styles are only used inside verses
+                if current.is_some() {
+                    target = Target::Verse;
+                }
+            }
+            // Paragraphs, poetry and lists continue the current verse
+            _ => target = Target::Verse,
+        }
+
+        // Word attributes, e.g. `\w gracious|strong="H2587"\w*`
+        if in_style && !options.keep_styles {
+            if let Some(end) = text.find('|') {
+                text = &text[..end];
+            }
+        }
+
+        // Paragraphs separate words even without spaces around them
+        if matches!(base, "p" | "m" | "q" | "pi" | "li" | "b" | "nb" | "mi" | "pc" | "qc" | "qr") {
+            if let Some(verse) = current.as_mut() {
+                verse.text.push(' ');
+            }
+        }
+        match target {
+            Target::Verse => {
+                if let Some(verse) = current.as_mut() {
+                    verse.text.push_str(text);
+                }
+            }
+            Target::Note => note.push_str(text),
+            Target::Skip => {}
+        }
+    }
+    finish(&mut verses, current.take());
+
+    (id, verses)
+}
+
+// The name of a book in the language of the translation, its header, e.g. `Johannes`.
+// Without a header the book is named from its `\id` code: the Italian name of a known code,
+// or the code itself
+fn book_name(id: Option<&str>, header: Option<&String>) -> String {
+    if let Some(header) = header {
+        return header.clone();
+    }
+    match id.and_then(|id| BOOKS.iter().find(|book| book.id == id)) {
+        Some(book) => book.italian_name().to_string(),
+        None => id.unwrap_or_default().to_string(),
+    }
+}
+
+// The leading number of a verse or chapter, e.g. 16 in `16-17` or `16a`
+fn first_number(text: &str) -> Option<u32> {
+    let digits: String = text.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok().filter(|number| *number > 0)
+}
+
+// Titles, headings, introductions and comments: their text is not part of the verses
+fn is_heading(base: &str) -> bool {
+    matches!(
+        base,
+        "ide" | "rem" | "sts" | "usfm" | "toc" | "toca" | "mt" | "mte" | "ms" | "mr" | "s" | "sr"
+            | "r" | "d" | "sp" | "cl" | "cd" | "imt" | "imte" | "is" | "ip" | "ipi" | "im"
+            | "imi" | "ipq" | "imq" | "ipr" | "iq" | "ib" | "ili" | "iot" | "io" | "iex" | "ie"
+    )
+}
+
+// Inline markers which change the look of some words, closed by the same marker with `*`
+fn is_character_style(base: &str) -> bool {
+    matches!(
+        base,
+        "add" | "bk" | "dc" | "k" | "nd" | "ord" | "pn" | "png" | "addpn" | "qt" | "sig" | "sls"
+            | "tl" | "wj" | "em" | "bd" | "it" | "bdit" | "no" | "sc" | "sup" | "w" | "wg" | "wh"
+            | "wa" | "pro" | "ndx" | "jmp" | "lik" | "liv" | "qs" | "qac" | "litl"
+    )
+}
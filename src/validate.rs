@@ -0,0 +1,252 @@
+use crate::bibleverse::versereader;
+use crate::books::book_key;
+use crate::json;
+use crate::usfm::UsfmOptions;
+use crate::verse::{Verse, VerseErrorKind};
+use crate::versesource::{self, Format};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, io,
+    io::BufRead,
+};
+
+/// A problem found in a bible file
+pub struct Problem {
+    /// Line number for the text files, verse number for the other formats, starting from 1
+    pub line: usize,
+    pub kind: ProblemKind,
+}
+
+pub enum ProblemKind {
+    /// The line is not a valid verse
+    Parse(VerseErrorKind),
+    NonUtf8,
+    /// The reference was already found at a line
+    Duplicate { reference: String, first: usize },
+    /// The verse comes after a following one
+    OutOfOrder { reference: String, previous: String },
+}
+
+/// What was found in a bible file
+pub struct Report {
+    pub verses: usize,
+    pub problems: Vec<Problem>,
+}
+
+/// `validate [--json] <file>`: prints the problems of a bible file and
+/// returns the exit code, 1 if there are problems and 2 if the file can not be read
+pub fn run(program_name: &str, args: &[String]) -> i32 {
+    let json = args.iter().any(|arg| arg == "--json");
+    let paths: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    let [path] = paths[..] else {
+        eprintln!("usage: {} validate [--json] <file>", program_name);
+        return 2;
+    };
+
+    let report = match validate(path) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{}: {}: {}", program_name, path, e);
+            return 2;
+        }
+    };
+
+    if json {
+        println!("{}", report.to_json(path));
+    } else {
+        for problem in &report.problems {
+            println!("{}:{}: {}", path, problem.line, problem.kind);
+        }
+        eprintln!(
+            "{}: {} verses, {} problems",
+            path,
+            report.verses,
+            report.problems.len()
+        );
+    }
+
+    if report.problems.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Checks every verse of a bible file. The text files are checked line by line,
+/// the other formats only for duplicate and out of order verses.
+pub fn validate(path: &str) -> io::Result<Report> {
+    let mut report = Report {
+        verses: 0,
+        problems: Vec::new(),
+    };
+    let mut order = OrderCheck::default();
+
+    if Format::detect(path)? != Format::Text {
+        let source = versesource::open(path, &UsfmOptions::default())?;
+        let mut position = 0;
+        source.scan(&mut |v| {
+            position += 1;
+            match v {
+                Ok(v) => {
+                    report.verses += 1;
+                    report.problems.extend(order.check(&v, position));
+                }
+                Err(e) => report.problems.push(Problem {
+                    line: position,
+                    kind: ProblemKind::Parse(e.kind),
+                }),
+            }
+        })?;
+
+        return Ok(report);
+    }
+
+    // Read bytes instead of strings to report the lines which are not valid UTF-8
+    let mut reader = versereader::BufReader::open(path)?;
+    let mut buffer = Vec::new();
+    let mut line_number = 0;
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            break;
+        }
+        line_number += 1;
+
+        let line = String::from_utf8_lossy(&buffer);
+        if std::str::from_utf8(&buffer).is_err() {
+            report.problems.push(Problem {
+                line: line_number,
+                kind: ProblemKind::NonUtf8,
+            });
+        }
+        // Blank lines are not verses
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match Verse::parse(&line, line_number) {
+            Ok(v) => {
+                report.verses += 1;
+                report.problems.extend(order.check(&v, line_number));
+            }
+            Err(e) => report.problems.push(Problem {
+                line: line_number,
+                kind: ProblemKind::Parse(e.kind),
+            }),
+        }
+    }
+
+    Ok(report)
+}
+
+// Finds the duplicate references and the verses out of order:
+// the verses of a book must be in order, and all together
+#[derive(Default)]
+struct OrderCheck {
+    // Line of each reference, by book key, chapter and verse
+    lines: HashMap<(String, u32, u32), usize>,
+    // The books before the current one
+    finished: HashSet<String>,
+    // Book key, chapter, verse and reference of the previous verse
+    previous: Option<(String, u32, u32, String)>,
+    // Book keys by book name
+    keys: HashMap<String, String>,
+}
+
+impl OrderCheck {
+    fn check(&mut self, verse: &Verse, line: usize) -> Option<Problem> {
+        let key = self
+            .keys
+            .entry(verse.book.clone())
+            .or_insert_with(|| book_key(&verse.book))
+            .clone();
+
+        let reference = (key.clone(), verse.chapter, verse.verse);
+        let problem = if let Some(&first) = self.lines.get(&reference) {
+            Some(ProblemKind::Duplicate {
+                reference: verse.reference(),
+                first,
+            })
+        } else {
+            self.lines.insert(reference, line);
+            match &self.previous {
+                Some((book, chapter, number, reference))
+                    if (*book == key && (*chapter, *number) > (verse.chapter, verse.verse))
+                        || (*book != key && self.finished.contains(&key)) =>
+                {
+                    Some(ProblemKind::OutOfOrder {
+                        reference: verse.reference(),
+                        previous: reference.clone(),
+                    })
+                }
+                _ => None,
+            }
+        };
+
+        if let Some((book, _, _, _)) = &self.previous {
+            if *book != key {
+                self.finished.insert(book.clone());
+            }
+        }
+        self.previous = Some((key, verse.chapter, verse.verse, verse.reference()));
+
+        problem.map(|kind| Problem { line, kind })
+    }
+}
+
+impl Report {
+    fn to_json(&self, path: &str) -> String {
+        let problems: Vec<String> = self
+            .problems
+            .iter()
+            .map(|problem| {
+                format!(
+                    "{{\"line\": {}, \"kind\": {}, \"message\": {}}}",
+                    problem.line,
+                    json::string(problem.kind.name()),
+                    json::string(&problem.kind.to_string())
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"file\": {}, \"valid\": {}, \"verses\": {}, \"problems\": [{}]}}",
+            json::string(path),
+            self.problems.is_empty(),
+            self.verses,
+            problems.join(", ")
+        )
+    }
+}
+
+impl ProblemKind {
+    /// A short name for the kind of problem, e.g. `missing_fields`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProblemKind::Parse(VerseErrorKind::MissingFields(_)) => "missing_fields",
+            ProblemKind::Parse(VerseErrorKind::EmptyBook) => "empty_book",
+            ProblemKind::Parse(VerseErrorKind::InvalidChapter(_)) => "invalid_chapter",
+            ProblemKind::Parse(VerseErrorKind::InvalidVerse(_)) => "invalid_verse",
+            ProblemKind::Parse(VerseErrorKind::EmptyText) => "empty_text",
+            ProblemKind::NonUtf8 => "non_utf8",
+            ProblemKind::Duplicate { .. } => "duplicate",
+            ProblemKind::OutOfOrder { .. } => "out_of_order",
+        }
+    }
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProblemKind::Parse(kind) => write!(f, "{}", kind),
+            ProblemKind::NonUtf8 => write!(f, "invalid UTF-8 bytes"),
+            ProblemKind::Duplicate { reference, first } => {
+                write!(f, "duplicate reference {}, first found at line {}", reference, first)
+            }
+            ProblemKind::OutOfOrder {
+                reference,
+                previous,
+            } => write!(f, "{} comes after {}", reference, previous),
+        }
+    }
+}
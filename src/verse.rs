@@ -0,0 +1,204 @@
+use std::fmt;
+
+// Separator between the fields of a line of the bible file
+pub const BIBLE_SEPARATOR: char = '|';
+
+/// A single verse, as read from a `book|chapter|verse|text` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verse {
+    pub book: String,
+    pub chapter: u32,
+    pub verse: u32,
+    pub text: String,
+}
+
+/// Why a line of the bible file could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerseErrorKind {
+    /// Less than four `|` separated fields, with the number of fields found
+    MissingFields(usize),
+    EmptyBook,
+    InvalidChapter(String),
+    InvalidVerse(String),
+    EmptyText,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerseError {
+    /// Line number in the bible file, starting from 1
+    pub line: usize,
+    pub kind: VerseErrorKind,
+}
+
+impl Verse {
+    /// Parses a `book|chapter|verse|text` line; `line_number` is only used for error reporting
+    pub fn parse(line: &str, line_number: usize) -> Result<Self, VerseError> {
+        let error = |kind| VerseError {
+            line: line_number,
+            kind,
+        };
+
+        // The text is the last field, so it may contain the separator itself
+        let fields: Vec<&str> = line.splitn(4, BIBLE_SEPARATOR).map(str::trim).collect();
+        if fields.len() < 4 {
+            return Err(error(VerseErrorKind::MissingFields(fields.len())));
+        }
+
+        let book = fields[0];
+        if book.is_empty() {
+            return Err(error(VerseErrorKind::EmptyBook));
+        }
+        let chapter = fields[1]
+            .parse::<u32>()
+            .ok()
+            .filter(|chapter| *chapter > 0)
+            .ok_or_else(|| error(VerseErrorKind::InvalidChapter(fields[1].to_string())))?;
+        let verse = fields[2]
+            .parse::<u32>()
+            .ok()
+            .filter(|verse| *verse > 0)
+            .ok_or_else(|| error(VerseErrorKind::InvalidVerse(fields[2].to_string())))?;
+        let text = fields[3];
+        if text.is_empty() {
+            return Err(error(VerseErrorKind::EmptyText));
+        }
+
+        Ok(Self {
+            book: book.to_string(),
+            chapter,
+            verse,
+            text: text.to_string(),
+        })
+    }
+
+    /// The verse reference, e.g. `Giovanni 3:16`
+    pub fn reference(&self) -> String {
+        format!("{} {}:{}", self.book, self.chapter, self.verse)
+    }
+}
+
+impl fmt::Display for Verse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}", self.reference(), self.text)
+    }
+}
+
+/// Verses shown together, usually consecutive verses of the same chapter
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Passage {
+    pub verses: Vec<Verse>,
+}
+
+impl Passage {
+    /// The reference of all the verses, e.g. `Giovanni 3:16-18`,
+    /// or `Salmi 23:1-4,6` when some verses are missing
+    pub fn reference(&self) -> String {
+        let Some(first) = self.verses.first() else {
+            return String::new();
+        };
+
+        // Runs of consecutive verses, by their first and last verse
+        let mut runs: Vec<(&Verse, &Verse)> = Vec::new();
+        for v in &self.verses {
+            match runs.last_mut() {
+                Some((_, last))
+                    if (v.chapter == last.chapter && v.verse == last.verse + 1)
+                        || (v.chapter == last.chapter + 1 && v.verse == 1) =>
+                {
+                    *last = v
+                }
+                _ => runs.push((v, v)),
+            }
+        }
+
+        let mut reference = first.book.clone();
+        let mut chapter = None;
+        for (start, end) in runs {
+            match chapter {
+                None => reference.push_str(&format!(" {}:{}", start.chapter, start.verse)),
+                Some(chapter) if chapter == start.chapter => {
+                    reference.push_str(&format!(",{}", start.verse))
+                }
+                Some(_) => reference.push_str(&format!("; {}:{}", start.chapter, start.verse)),
+            }
+            if end.chapter != start.chapter {
+                reference.push_str(&format!("-{}:{}", end.chapter, end.verse));
+            } else if end.verse != start.verse {
+                reference.push_str(&format!("-{}", end.verse));
+            }
+            chapter = Some(end.chapter);
+        }
+
+        reference
+    }
+
+    /// The text of all the verses
+    pub fn text(&self) -> String {
+        self.verses
+            .iter()
+            .map(|v| v.text.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.verses.is_empty()
+    }
+}
+
+impl fmt::Display for Passage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}", self.reference(), self.text())
+    }
+}
+
+impl fmt::Display for VerseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerseErrorKind::MissingFields(found) => {
+                write!(f, "expected 4 fields separated by '{}', found {}", BIBLE_SEPARATOR, found)
+            }
+            VerseErrorKind::EmptyBook => write!(f, "empty book name"),
+            VerseErrorKind::InvalidChapter(chapter) => write!(f, "invalid chapter number '{}'", chapter),
+            VerseErrorKind::InvalidVerse(verse) => write!(f, "invalid verse number '{}'", verse),
+            VerseErrorKind::EmptyText => write!(f, "empty verse text"),
+        }
+    }
+}
+
+impl fmt::Display for VerseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for VerseError {}
+
+/// Adds a verse read from a document with its whitespace collapsed, skipping the empty ones
+pub fn finish(verses: &mut Vec<Verse>, current: Option<Verse>) {
+    if let Some(mut verse) = current {
+        verse.text = verse.text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if !verse.text.is_empty() {
+            verses.push(verse);
+        }
+    }
+}
+
+/// Formats a text to max `line_length` characters per line by adding \n
+pub fn wrap(text: &str, line_length: i32) -> String {
+    let mut i: i32 = 0;
+    let mut wrapped = String::new();
+    for word in text.split_whitespace() {
+        let count: i32 = word.chars().count().try_into().unwrap();
+        if (i + count) > line_length {
+            wrapped.push('\n');
+            i = 0;
+        } else {
+            i += count;
+        }
+        wrapped.push_str(word);
+        wrapped.push(' ');
+    }
+
+    wrapped
+}
@@ -0,0 +1,119 @@
+use crate::books::{book_key, Book, Testament};
+use crate::reference::{Position, Reference};
+use crate::verse::Verse;
+
+// Separates the books of -books
+const BOOKS_SEPARATOR: char = ',';
+
+// Separates the ranges of -range
+const RANGES_SEPARATOR: char = ';';
+
+/// Restricts the verses that can be shown to some books, a testament
+/// or some reference ranges. An empty filter accepts every verse.
+#[derive(Debug, Clone, Default)]
+pub struct VerseFilter {
+    // Keys of the accepted books, see books::book_key
+    books: Vec<String>,
+    testament: Option<Testament>,
+    ranges: Vec<Reference>,
+}
+
+impl VerseFilter {
+    /// Builds a filter from the values of the -books, -testament and -range options, e.g.
+    /// `Genesi, Gv`, `old` and `Psalms 1-150; Proverbs; Giovanni 3:16-4:2`
+    pub fn new(
+        books: Option<&str>,
+        testament: Option<&str>,
+        ranges: Option<&str>,
+    ) -> Result<Self, String> {
+        let mut filter = Self::default();
+
+        if let Some(books) = books {
+            filter.books = books
+                .split(BOOKS_SEPARATOR)
+                .map(str::trim)
+                .filter(|book| !book.is_empty())
+                .map(book_key)
+                .collect();
+        }
+
+        if let Some(testament) = testament {
+            filter.testament = match testament.trim().to_lowercase().as_str() {
+                "old" | "ot" | "antico" | "at" => Some(Testament::Old),
+                "new" | "nt" | "nuovo" => Some(Testament::New),
+                _ => return Err(format!("invalid testament '{}', use old or new", testament)),
+            };
+        }
+
+        if let Some(ranges) = ranges {
+            for range in ranges.split(RANGES_SEPARATOR).map(str::trim) {
+                if !range.is_empty() {
+                    filter.ranges.push(Reference::parse(range)?);
+                }
+            }
+        }
+
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.books.is_empty() && self.testament.is_none() && self.ranges.is_empty()
+    }
+
+    /// True if the verse matches every restriction of the filter
+    pub fn accepts(&self, verse: &Verse) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+
+        let key = book_key(&verse.book);
+        if !self.books.is_empty() && !self.books.contains(&key) {
+            return false;
+        }
+
+        if let Some(testament) = self.testament {
+            // The testament of an unknown book is unknown too
+            match Book::find(&verse.book) {
+                Some(book) if book.testament == testament => {}
+                _ => return false,
+            }
+        }
+
+        self.ranges.is_empty()
+            || self.ranges.iter().any(|range| {
+                range.book_key() == key && range.contains_position(verse.chapter, verse.verse)
+            })
+    }
+
+    /// The (chapter, verse) ranges of a book accepted by the filter, both included,
+    /// or None if no verse of the book is accepted. Used to filter the verses in SQL.
+    pub fn book_ranges(&self, book: &str) -> Option<Vec<(Position, Position)>> {
+        let whole_book = vec![((0, 0), (u32::MAX, u32::MAX))];
+        if self.is_empty() {
+            return Some(whole_book);
+        }
+
+        let key = book_key(book);
+        if !self.books.is_empty() && !self.books.contains(&key) {
+            return None;
+        }
+
+        if let Some(testament) = self.testament {
+            match Book::find(book) {
+                Some(book) if book.testament == testament => {}
+                _ => return None,
+            }
+        }
+
+        if self.ranges.is_empty() {
+            return Some(whole_book);
+        }
+        let ranges: Vec<(Position, Position)> = self
+            .ranges
+            .iter()
+            .filter(|range| range.book_key() == key)
+            .flat_map(|range| range.ranges.iter().copied())
+            .collect();
+        (!ranges.is_empty()).then_some(ranges)
+    }
+}
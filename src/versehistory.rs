@@ -0,0 +1,105 @@
+use crate::verse::Verse;
+use std::{
+    collections::{HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The most recently shown verses, persisted across runs so the same
+/// verse is not shown again until `window` other verses have been shown.
+pub struct VerseHistory {
+    path: Option<PathBuf>,
+    window: usize,
+    // Verse references, oldest first
+    entries: VecDeque<String>,
+    shown: HashSet<String>,
+}
+
+impl VerseHistory {
+    /// Loads the history from $XDG_STATE_HOME/e4biblesaver/history
+    /// (~/.local/state by default). A window of 0 disables the history.
+    pub fn load(window: usize) -> Self {
+        let path = std::env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))
+            .map(|dir| dir.join("e4biblesaver").join("history"));
+
+        let mut history = Self {
+            path,
+            ..Self::in_memory(window)
+        };
+
+        if let Some(content) = history.path.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+            for reference in content.lines().filter(|line| !line.trim().is_empty()) {
+                history.insert(reference.to_string());
+            }
+        }
+
+        history
+    }
+
+    /// A history which is neither loaded nor saved, e.g. to reproduce a run with a seed
+    pub fn in_memory(window: usize) -> Self {
+        Self {
+            path: None,
+            window,
+            entries: VecDeque::new(),
+            shown: HashSet::new(),
+        }
+    }
+
+    pub fn contains(&self, verse: &Verse) -> bool {
+        self.shown.contains(&verse.reference())
+    }
+
+    /// Remembers the verses of a passage just shown and saves the history once
+    pub fn push_all<'a>(&mut self, verses: impl IntoIterator<Item = &'a Verse>) {
+        if self.window == 0 {
+            return;
+        }
+
+        for verse in verses {
+            self.insert(verse.reference());
+        }
+        self.save();
+    }
+
+    /// Forgets every verse, when all of them have been shown
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.shown.clear();
+        self.save();
+    }
+
+    fn insert(&mut self, reference: String) {
+        if self.shown.insert(reference.clone()) {
+            self.entries.push_back(reference);
+        }
+
+        while self.entries.len() > self.window {
+            if let Some(oldest) = self.entries.pop_front() {
+                self.shown.remove(&oldest);
+            }
+        }
+    }
+
+    // The history is not essential: errors are ignored
+    fn save(&self) {
+        if self.window == 0 {
+            return;
+        }
+
+        if let Some(path) = &self.path {
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            let mut content = String::new();
+            for reference in &self.entries {
+                content.push_str(reference);
+                content.push('\n');
+            }
+            let _ = fs::write(path, content);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::books;
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+// The topics of -topic, by their English and Italian names, with the words they match
+const TOPICS: &[(&[&str], &str)] = &[
+    (&["peace", "pace"], r"\b(pace|paci|pacific\w*|peace\w*)\b"),
+    (&["joy", "gioia"], r"\b(gioi\w*|allegr\w*|letizia|esult\w*|joy\w*|rejoic\w*|glad\w*)\b"),
+    (
+        &["love", "amore"],
+        concat!(
+            r"\b(amor[ei]?|ama(re|to|ti|ta|te|no|va|vano)?|amo|ami|amiamo|carita|",
+            r"love[sdr]?|loving|beloved|charity)\b"
+        ),
+    ),
+    (&["hope", "speranza"], r"\b(speranz\w*|spera\w*|spero|hope[sd]?|hoping)\b"),
+    (&["faith", "fede"], r"\b(fede|fedel\w*|fiducia|cred\w*|faith\w*|believ\w*|trust\w*)\b"),
+    (&["forgiveness", "perdono"], r"\b(perdon\w*|rimess\w*|forgiv\w*|pardon\w*)\b"),
+    (&["light", "luce"], r"\b(luce|luci|lucern\w*|light\w*)\b"),
+    (&["comfort", "consolazione"], r"\b(consol\w*|confort\w*|comfort\w*)\b"),
+    (&["grace", "grazia"], r"\b(grazia|grace|gracious)\b"),
+    (&["prayer", "preghiera"], r"\b(preghier\w*|preg[ahio]\w*|pray\w*)\b"),
+];
+
+/// Finds a word or a regular expression in the text of the verses, ignoring case
+/// and accents, so that `perche` finds `perché` and `GIOIA` finds `gioia`
+#[derive(Debug, Clone)]
+pub struct VerseMatcher {
+    regex: Regex,
+}
+
+impl VerseMatcher {
+    /// A matcher for a plain text, or for a regular expression if `is_regex`
+    pub fn new(pattern: &str, is_regex: bool) -> Result<Self, String> {
+        let pattern = fold_accents(pattern.trim());
+        if pattern.is_empty() {
+            return Err(String::from("empty search pattern"));
+        }
+        let pattern = match is_regex {
+            true => pattern,
+            false => regex::escape(&pattern),
+        };
+
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| format!("invalid pattern: {}", e))?;
+
+        Ok(Self { regex })
+    }
+
+    /// A matcher for the words of a topic, e.g. `peace` or `gioia`,
+    /// or for the whole word itself if it is not a known topic
+    pub fn topic(name: &str) -> Result<Self, String> {
+        let key = fold_accents(name.trim()).to_lowercase();
+        match TOPICS.iter().find(|(names, _)| names.contains(&key.as_str())) {
+            Some((_, pattern)) => Self::new(pattern, true),
+            None => Self::new(&format!(r"\b{}\b", regex::escape(&key)), true),
+        }
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(&fold_accents(text))
+    }
+
+    /// The byte ranges of the matches in the text
+    pub fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        // Offsets in the text of each byte of the folded text, which may be shorter
+        let mut offsets = Vec::with_capacity(text.len() + 1);
+        let mut folded = String::with_capacity(text.len());
+        for (offset, c) in text.char_indices() {
+            let c = fold_accent(c);
+            offsets.extend(std::iter::repeat_n(offset, c.len_utf8()));
+            folded.push(c);
+        }
+        offsets.push(text.len());
+
+        self.regex
+            .find_iter(&folded)
+            .filter(|found| !found.is_empty())
+            .map(|found| offsets[found.start()]..offsets[found.end()])
+            .collect()
+    }
+}
+
+// Removes the accents, one character for another so that the matches can be mapped back
+fn fold_accents(text: &str) -> String {
+    text.chars().map(fold_accent).collect()
+}
+
+// Typographic apostrophes are folded too, e.g. `dell’uomo`
+fn fold_accent(c: char) -> char {
+    match c {
+        '’' | '‘' => '\'',
+        c => books::fold_accent(c),
+    }
+}
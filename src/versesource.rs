@@ -0,0 +1,229 @@
+use crate::bibleindex::BibleIndex;
+use crate::bibleverse::versereader;
+use crate::osis;
+use crate::sqlitebible::SqliteSource;
+use crate::usfm::{self, UsfmOptions};
+use crate::verse::{Verse, VerseError};
+use crate::versefilter::VerseFilter;
+use crate::xmlbible;
+use std::{
+    io,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// Where the verses come from. The selection in bibleverse only needs to read
+/// a verse by position, when the source allows it, or to scan every verse.
+pub trait VerseSource {
+    /// Number of verses which can be read with `get`, None if the source can only be scanned
+    fn len(&self) -> Option<usize>;
+
+    /// The verse at a position lower than `len`, or the reason it can not be parsed.
+    /// None if the source changed and can only be scanned.
+    fn get(&self, position: usize) -> Option<Result<Verse, VerseError>>;
+
+    /// Calls `f` on every verse, in the order of the source
+    fn scan(&self, f: &mut dyn FnMut(Result<Verse, VerseError>)) -> io::Result<()>;
+
+    /// Leaves out the verses not accepted by the filter, when the source can do it
+    /// better than checking every verse. The other sources are left as they are.
+    fn restrict(&mut self, _filter: &VerseFilter) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A `book|chapter|verse|text` file, read through its index when available
+pub struct TextSource {
+    path: String,
+    index: Option<BibleIndex>,
+}
+
+/// Verses kept in memory, e.g. read from an XML document
+pub struct MemorySource {
+    verses: Vec<Verse>,
+}
+
+/// The formats a bible can be read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `book|chapter|verse|text` lines
+    Text,
+    Osis,
+    /// A directory of USFM files, or a single one
+    Usfm,
+    Zefania,
+    Beblia,
+    Sqlite,
+}
+
+impl Format {
+    /// Recognizes the format of a bible from its first bytes
+    pub fn detect(path: &str) -> io::Result<Self> {
+        if Path::new(path).is_dir() {
+            return Ok(Format::Usfm);
+        }
+
+        // Compressed files are recognized from their content
+        let mut head = Vec::new();
+        versereader::BufReader::open(path)?.take(1024).read_to_end(&mut head)?;
+        let head = String::from_utf8_lossy(&head);
+        let head = head.trim_start_matches('\u{feff}').trim_start();
+
+        // The XML documents are recognized from their root element
+        let xml = head.starts_with('<');
+        let format = if head.starts_with("SQLite format 3\0") {
+            Format::Sqlite
+        } else if head.starts_with("\\id ") {
+            Format::Usfm
+        } else if xml && head.contains("<osis") {
+            Format::Osis
+        } else if xml && head.contains("<XMLBIBLE") {
+            Format::Zefania
+        } else if xml && head.contains("<bible") {
+            Format::Beblia
+        } else {
+            Format::Text
+        };
+
+        Ok(format)
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    /// Parses the name of a format, e.g. `osis`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "pipe" | "text" | "txt" => Ok(Format::Text),
+            "osis" => Ok(Format::Osis),
+            "usfm" | "sfm" => Ok(Format::Usfm),
+            "zefania" => Ok(Format::Zefania),
+            "beblia" => Ok(Format::Beblia),
+            "sqlite" | "sqlite3" => Ok(Format::Sqlite),
+            _ => Err(format!(
+                "unknown format '{}', use pipe, osis, usfm, zefania, beblia or sqlite",
+                name
+            )),
+        }
+    }
+}
+
+/// The bible installed with the screensaver, `/opt/<program name>/bible.txt`
+pub fn default_path(program_name: &str) -> String {
+    let mut path = PathBuf::from("/opt");
+    path.push(program_name);
+    path.push("bible.txt");
+    path.to_string_lossy().into_owned()
+}
+
+/// Opens a bible file in any of the supported formats, or a directory of USFM files
+pub fn open(path: &str, usfm: &UsfmOptions) -> io::Result<Box<dyn VerseSource>> {
+    open_as(path, Format::detect(path)?, usfm)
+}
+
+/// Opens a bible which is only scanned: a text file is read without writing its index
+pub fn open_for_scan(path: &str, usfm: &UsfmOptions) -> io::Result<Box<dyn VerseSource>> {
+    match Format::detect(path)? {
+        Format::Text => Ok(Box::new(TextSource::without_index(path))),
+        format => open_as(path, format, usfm),
+    }
+}
+
+/// Opens a bible file in a given format
+pub fn open_as(path: &str, format: Format, usfm: &UsfmOptions) -> io::Result<Box<dyn VerseSource>> {
+    let verses = match format {
+        Format::Text => return Ok(Box::new(TextSource::open(path))),
+        Format::Sqlite => return Ok(Box::new(SqliteSource::open(path)?)),
+        Format::Osis => osis::read(Path::new(path))?,
+        Format::Usfm if Path::new(path).is_dir() => usfm::read_dir(Path::new(path), usfm)?,
+        Format::Usfm => usfm::read(Path::new(path), usfm)?,
+        Format::Zefania => xmlbible::read_zefania(Path::new(path))?,
+        Format::Beblia => xmlbible::read_beblia(Path::new(path))?,
+    };
+
+    // The XML and USFM documents are read once and kept in memory
+    Ok(Box::new(MemorySource::new(verses)))
+}
+
+impl TextSource {
+    /// A text file read only with full scans, without writing its index
+    pub fn without_index(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            index: None,
+        }
+    }
+
+    pub fn open(path: &str) -> Self {
+        // A compressed file can not be read at random positions: its decompressed copy is
+        // used instead, or the file is decompressed at every scan if there is no copy
+        let path = match versereader::is_compressed(path) {
+            Ok(true) => match BibleIndex::decompressed_copy(Path::new(path)) {
+                Ok(copy) => copy.to_string_lossy().into_owned(),
+                Err(_) => return Self::without_index(path),
+            },
+            _ => path.to_string(),
+        };
+
+        // Build or load the verse index once, the file will be rescanned if it is not available
+        let index = BibleIndex::load_or_build(Path::new(&path)).ok();
+
+        Self { path, index }
+    }
+}
+
+impl VerseSource for TextSource {
+    fn len(&self) -> Option<usize> {
+        self.index.as_ref().map(BibleIndex::len)
+    }
+
+    fn get(&self, position: usize) -> Option<Result<Verse, VerseError>> {
+        match self.index.as_ref()?.read_line(Path::new(&self.path), position) {
+            Ok(Some((line_number, line))) => Some(Verse::parse(&line, line_number)),
+            // The file changed since the index was built
+            _ => None,
+        }
+    }
+
+    fn scan(&self, f: &mut dyn FnMut(Result<Verse, VerseError>)) -> io::Result<()> {
+        let mut reader = versereader::BufReader::open(&self.path)?;
+
+        let mut buffer = String::new();
+        let mut line_number = 0;
+        while let Some(line) = reader.read_line(&mut buffer) {
+            let line = line?;
+            line_number += 1;
+            // Blank lines are not verses, the index skips them too
+            if !line.trim().is_empty() {
+                f(Verse::parse(line, line_number));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MemorySource {
+    pub fn new(verses: Vec<Verse>) -> Self {
+        Self { verses }
+    }
+}
+
+impl VerseSource for MemorySource {
+    fn len(&self) -> Option<usize> {
+        Some(self.verses.len())
+    }
+
+    fn get(&self, position: usize) -> Option<Result<Verse, VerseError>> {
+        self.verses.get(position).cloned().map(Ok)
+    }
+
+    fn scan(&self, f: &mut dyn FnMut(Result<Verse, VerseError>)) -> io::Result<()> {
+        for v in &self.verses {
+            f(Ok(v.clone()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+use quick_xml::events::BytesStart;
+
+/// The value of an attribute, if present and well formed, without surrounding whitespace
+pub fn attribute(e: &BytesStart, name: &[u8]) -> Option<String> {
+    e.try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|a| a.unescape_value().ok())
+        .map(|value| value.trim().to_string())
+}
@@ -0,0 +1,174 @@
+use crate::bibleverse::versereader;
+use crate::books::Book;
+use crate::verse::{finish, Verse};
+use crate::xml::attribute;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::{io, path::Path};
+
+// Element and attribute names of a XML bible made of numbered books, chapters and verses
+struct Schema {
+    book: &'static [u8],
+    book_number: &'static [u8],
+    book_name: &'static [u8],
+    chapter: &'static [u8],
+    chapter_number: &'static [u8],
+    verse: &'static [u8],
+    verse_number: &'static [u8],
+    // Elements inside the verses whose text is not part of them
+    skip: &'static [&'static [u8]],
+}
+
+// `<XMLBIBLE><BIBLEBOOK bnumber="43" bname="John"><CHAPTER cnumber="3"><VERS vnumber="16">`
+const ZEFANIA: Schema = Schema {
+    book: b"BIBLEBOOK",
+    book_number: b"bnumber",
+    book_name: b"bname",
+    chapter: b"CHAPTER",
+    chapter_number: b"cnumber",
+    verse: b"VERS",
+    verse_number: b"vnumber",
+    skip: &[b"NOTE", b"XREF", b"REMARK", b"MEDIA"],
+};
+
+// `<bible><testament><book number="43"><chapter number="3"><verse number="16">`
+const BEBLIA: Schema = Schema {
+    book: b"book",
+    book_number: b"number",
+    book_name: b"name",
+    chapter: b"chapter",
+    chapter_number: b"number",
+    verse: b"verse",
+    verse_number: b"number",
+    skip: &[b"note"],
+};
+
+/// Reads the verses of a Zefania XML bible
+pub fn read_zefania(path: &Path) -> io::Result<Vec<Verse>> {
+    read(path, &ZEFANIA)
+}
+
+/// Reads the verses of a Beblia XML bible
+pub fn read_beblia(path: &Path) -> io::Result<Vec<Verse>> {
+    read(path, &BEBLIA)
+}
+
+// Books without a name are named from their number, in Italian when
+// the language of the document is Italian and in English otherwise
+fn read(path: &Path, schema: &Schema) -> io::Result<Vec<Verse>> {
+    let mut reader = Reader::from_reader(versereader::BufReader::open(path)?);
+
+    let mut verses = Vec::new();
+    let mut italian = false;
+    let mut in_language = false;
+    let mut book = String::new();
+    let mut chapter = 0;
+    // The verse being read, with its text so far
+    let mut current: Option<Verse> = None;
+    // Depth of the elements whose text is skipped
+    let mut skip: usize = 0;
+    let mut root = true;
+    let mut buffer = Vec::new();
+    loop {
+        let event = reader.read_event_into(&mut buffer).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("position {}: {}", reader.error_position(), e),
+            )
+        })?;
+        match event {
+            Event::Start(e) => {
+                let name = e.local_name();
+                let name = name.as_ref();
+                if root {
+                    root = false;
+                    italian = ["language", "lang", "xml:lang", "translation"]
+                        .iter()
+                        .filter_map(|name| attribute(&e, name.as_bytes()))
+                        .any(|language| language.to_lowercase().starts_with("it"));
+                }
+                if name.eq_ignore_ascii_case(b"language") {
+                    in_language = true;
+                } else if name == schema.book {
+                    book = book_name(&e, schema, italian);
+                } else if name == schema.chapter {
+                    chapter = number(&e, schema.chapter_number).unwrap_or(0);
+                } else if name == schema.verse {
+                    finish(&mut verses, current.take());
+                    current = match number(&e, schema.verse_number) {
+                        Some(verse) if chapter > 0 && !book.is_empty() => Some(Verse {
+                            book: book.clone(),
+                            chapter,
+                            verse,
+                            text: String::new(),
+                        }),
+                        _ => None,
+                    };
+                } else if schema.skip.contains(&name) {
+                    skip += 1;
+                }
+            }
+            // Line breaks separate words
+            Event::Empty(e) if e.local_name().as_ref().eq_ignore_ascii_case(b"br") => {
+                if let Some(verse) = current.as_mut() {
+                    verse.text.push(' ');
+                }
+            }
+            Event::End(e) => {
+                let name = e.local_name();
+                let name = name.as_ref();
+                if name.eq_ignore_ascii_case(b"language") {
+                    in_language = false;
+                } else if name == schema.verse {
+                    finish(&mut verses, current.take());
+                } else if schema.skip.contains(&name) {
+                    skip = skip.saturating_sub(1);
+                }
+            }
+            Event::Text(e) => {
+                let unescaped = e
+                    .unescape()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                if in_language {
+                    italian = unescaped.trim().to_lowercase().starts_with("it");
+                } else if skip == 0 {
+                    if let Some(verse) = current.as_mut() {
+                        verse.text.push_str(&unescaped);
+                    }
+                }
+            }
+            Event::CData(e) if skip == 0 => {
+                if let Some(verse) = current.as_mut() {
+                    verse.text.push_str(&String::from_utf8_lossy(&e));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buffer.clear();
+    }
+    finish(&mut verses, current.take());
+
+    Ok(verses)
+}
+
+// A chapter or verse number, e.g. 16 in `16` or `16-17`
+fn number(e: &BytesStart, name: &[u8]) -> Option<u32> {
+    let value = attribute(e, name)?;
+    let digits: String = value.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok().filter(|number| *number > 0)
+}
+
+// The name given in the document, or the one of the book number
+fn book_name(e: &BytesStart, schema: &Schema, italian: bool) -> String {
+    if let Some(name) = attribute(e, schema.book_name).filter(|name| !name.is_empty()) {
+        return name;
+    }
+
+    let number = attribute(e, schema.book_number).unwrap_or_default();
+    match number.parse().ok().and_then(Book::from_number) {
+        Some(book) if italian => book.italian_name().to_string(),
+        Some(book) => book.english_name().to_string(),
+        None => number,
+    }
+}